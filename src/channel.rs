@@ -0,0 +1,540 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use {AddedChannelMode, ChannelModeChange, ChannelModeKind, ChannelModeTable, RemovedChannelMode};
+
+/// The modes of a channel, kept up to date by applying the changes the server announces.
+///
+/// Flags are kept by their mode letter, so that flags without a variant of their own are tracked
+/// alongside the rest. Other modes with an argument are kept as the `ChannelModeTable` passed to
+/// `apply` says: list and membership modes as a set of entries per letter, and anything else as a
+/// setting of a single value.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct ChannelModes {
+    flags: BTreeSet<char>,
+    key: Option<String>,
+    limit: Option<u64>,
+    settings: BTreeMap<char, String>,
+
+    bans: BTreeSet<String>,
+    ban_exceptions: BTreeSet<String>,
+    invite_exceptions: BTreeSet<String>,
+    quiets: BTreeSet<String>,
+
+    ops: BTreeSet<String>,
+    voices: BTreeSet<String>,
+    halfops: BTreeSet<String>,
+    admins: BTreeSet<String>,
+    owners: BTreeSet<String>,
+
+    // List and membership modes without a variant of their own.
+    lists: BTreeMap<char, BTreeSet<String>>,
+    members: BTreeMap<char, BTreeSet<String>>,
+}
+
+impl ChannelModes {
+    pub fn new() -> ChannelModes {
+        ChannelModes::default()
+    }
+
+    /// Applies a change, using `table` to tell how a mode without a variant of its own is kept.
+    pub fn apply(&mut self, change: &ChannelModeChange, table: &ChannelModeTable) {
+        match *change {
+            ChannelModeChange::Added(ref mode) => self.add(mode, table),
+            ChannelModeChange::Removed(ref mode) => self.remove(mode, table),
+            ChannelModeChange::ListQuery(_) => {}
+        }
+    }
+
+    pub fn apply_all(&mut self, changes: &[ChannelModeChange], table: &ChannelModeTable) {
+        for change in changes {
+            self.apply(change, table);
+        }
+    }
+
+    /// The entries set on the list or membership mode without a variant of its own.
+    fn entries(&mut self, mode: char, table: &ChannelModeTable) -> Option<&mut BTreeSet<String>> {
+        match table.kind(mode) {
+            Some(ChannelModeKind::List) => Some(self.lists.entry(mode).or_default()),
+            Some(ChannelModeKind::Prefix) => Some(self.members.entry(mode).or_default()),
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, mode: &AddedChannelMode, table: &ChannelModeTable) {
+        match *mode {
+            AddedChannelMode::Op(ref nick) => {
+                self.ops.insert(nick.clone());
+            }
+            AddedChannelMode::Voice(ref nick) => {
+                self.voices.insert(nick.clone());
+            }
+            AddedChannelMode::HalfOp(ref nick) => {
+                self.halfops.insert(nick.clone());
+            }
+            AddedChannelMode::Admin(ref nick) => {
+                self.admins.insert(nick.clone());
+            }
+            AddedChannelMode::Owner(ref nick) => {
+                self.owners.insert(nick.clone());
+            }
+
+            AddedChannelMode::Anonymous => {
+                self.flags.insert('a');
+            }
+            AddedChannelMode::InviteOnly => {
+                self.flags.insert('i');
+            }
+            AddedChannelMode::Moderated => {
+                self.flags.insert('m');
+            }
+            AddedChannelMode::NoExternal => {
+                self.flags.insert('n');
+            }
+            AddedChannelMode::Quiet(ref mask) => {
+                self.quiets.insert(mask.clone());
+            }
+            AddedChannelMode::Private => {
+                self.flags.insert('p');
+            }
+            AddedChannelMode::Secret => {
+                self.flags.insert('s');
+            }
+            AddedChannelMode::Reop => {
+                self.flags.insert('r');
+            }
+            AddedChannelMode::OpsTopic => {
+                self.flags.insert('t');
+            }
+            AddedChannelMode::NoColors => {
+                self.flags.insert('c');
+            }
+            AddedChannelMode::NoCtcp => {
+                self.flags.insert('C');
+            }
+            AddedChannelMode::RegisteredOnly => {
+                self.flags.insert('R');
+            }
+            AddedChannelMode::StripColors => {
+                self.flags.insert('S');
+            }
+            AddedChannelMode::SecureOnly => {
+                self.flags.insert('z');
+            }
+            AddedChannelMode::NoKicks => {
+                self.flags.insert('Q');
+            }
+
+            AddedChannelMode::Key(ref key) => self.key = Some(key.clone()),
+            AddedChannelMode::Limit(limit) => self.limit = Some(limit),
+            AddedChannelMode::JoinThrottle(ref value) => {
+                self.settings.insert('j', value.clone());
+            }
+            AddedChannelMode::Flood(ref value) => {
+                self.settings.insert('f', value.clone());
+            }
+            AddedChannelMode::Link(ref value) => {
+                self.settings.insert('L', value.clone());
+            }
+
+            AddedChannelMode::Ban(ref mask) => {
+                self.bans.insert(mask.clone());
+            }
+            AddedChannelMode::BanException(ref mask) => {
+                self.ban_exceptions.insert(mask.clone());
+            }
+            AddedChannelMode::InviteException(ref mask) => {
+                self.invite_exceptions.insert(mask.clone());
+            }
+
+            AddedChannelMode::Other(mode, None) => {
+                self.flags.insert(mode);
+            }
+            AddedChannelMode::Other(mode, Some(ref value)) => match self.entries(mode, table) {
+                Some(entries) => {
+                    entries.insert(value.clone());
+                }
+                None => {
+                    self.settings.insert(mode, value.clone());
+                }
+            },
+        }
+    }
+
+    fn remove(&mut self, mode: &RemovedChannelMode, table: &ChannelModeTable) {
+        match *mode {
+            RemovedChannelMode::Op(ref nick) => {
+                self.ops.remove(nick);
+            }
+            RemovedChannelMode::Voice(ref nick) => {
+                self.voices.remove(nick);
+            }
+            RemovedChannelMode::HalfOp(ref nick) => {
+                self.halfops.remove(nick);
+            }
+            RemovedChannelMode::Admin(ref nick) => {
+                self.admins.remove(nick);
+            }
+            RemovedChannelMode::Owner(ref nick) => {
+                self.owners.remove(nick);
+            }
+
+            RemovedChannelMode::Anonymous => {
+                self.flags.remove(&'a');
+            }
+            RemovedChannelMode::InviteOnly => {
+                self.flags.remove(&'i');
+            }
+            RemovedChannelMode::Moderated => {
+                self.flags.remove(&'m');
+            }
+            RemovedChannelMode::NoExternal => {
+                self.flags.remove(&'n');
+            }
+            RemovedChannelMode::Quiet(ref mask) => {
+                self.quiets.remove(mask);
+            }
+            RemovedChannelMode::Private => {
+                self.flags.remove(&'p');
+            }
+            RemovedChannelMode::Secret => {
+                self.flags.remove(&'s');
+            }
+            RemovedChannelMode::Reop => {
+                self.flags.remove(&'r');
+            }
+            RemovedChannelMode::OpsTopic => {
+                self.flags.remove(&'t');
+            }
+            RemovedChannelMode::NoColors => {
+                self.flags.remove(&'c');
+            }
+            RemovedChannelMode::NoCtcp => {
+                self.flags.remove(&'C');
+            }
+            RemovedChannelMode::RegisteredOnly => {
+                self.flags.remove(&'R');
+            }
+            RemovedChannelMode::StripColors => {
+                self.flags.remove(&'S');
+            }
+            RemovedChannelMode::SecureOnly => {
+                self.flags.remove(&'z');
+            }
+            RemovedChannelMode::NoKicks => {
+                self.flags.remove(&'Q');
+            }
+
+            RemovedChannelMode::Key(_) => self.key = None,
+            RemovedChannelMode::Limit => self.limit = None,
+            RemovedChannelMode::JoinThrottle(_) => {
+                self.settings.remove(&'j');
+            }
+            RemovedChannelMode::Flood(_) => {
+                self.settings.remove(&'f');
+            }
+            RemovedChannelMode::Link(_) => {
+                self.settings.remove(&'L');
+            }
+
+            RemovedChannelMode::Ban(ref mask) => {
+                self.bans.remove(mask);
+            }
+            RemovedChannelMode::BanException(ref mask) => {
+                self.ban_exceptions.remove(mask);
+            }
+            RemovedChannelMode::InviteException(ref mask) => {
+                self.invite_exceptions.remove(mask);
+            }
+
+            RemovedChannelMode::Other(mode, ref value) => match self.entries(mode, table) {
+                Some(entries) => {
+                    if let Some(ref value) = *value {
+                        entries.remove(value);
+                    }
+                    if entries.is_empty() {
+                        self.lists.remove(&mode);
+                        self.members.remove(&mode);
+                    }
+                }
+                None => {
+                    self.flags.remove(&mode);
+                    self.settings.remove(&mode);
+                }
+            },
+        }
+    }
+
+    /// The changes that turn these modes into `target`, with every removal ahead of every
+    /// addition.
+    ///
+    /// A changed key is removed before the new one is set, as servers refuse to replace a key.
+    /// Other settings are removed with their current value when `table` lists their mode as
+    /// always taking an argument, and without it otherwise.
+    pub fn diff(&self, target: &ChannelModes, table: &ChannelModeTable) -> Vec<ChannelModeChange> {
+        fn lists(
+            current: &BTreeSet<String>,
+            target: &BTreeSet<String>,
+            removed: &mut Vec<RemovedChannelMode>,
+            added: &mut Vec<AddedChannelMode>,
+            remove: fn(String) -> RemovedChannelMode,
+            add: fn(String) -> AddedChannelMode,
+        ) {
+            removed.extend(current.difference(target).cloned().map(remove));
+            added.extend(target.difference(current).cloned().map(add));
+        }
+
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+
+        for &mode in self.flags.difference(&target.flags) {
+            removed.push(RemovedChannelMode::new(mode, ChannelModeKind::Flag, None));
+        }
+        for &mode in target.flags.difference(&self.flags) {
+            added.push(AddedChannelMode::new(mode, ChannelModeKind::Flag, None));
+        }
+
+        if self.key != target.key {
+            if let Some(ref key) = self.key {
+                removed.push(RemovedChannelMode::Key(Some(key.clone())));
+            }
+            if let Some(ref key) = target.key {
+                added.push(AddedChannelMode::Key(key.clone()));
+            }
+        }
+        // How a setting is removed, which needs its value for a mode in group B of CHANMODES.
+        let remove_setting = |mode: char, value: String| {
+            let kind = table
+                .kind(mode)
+                .unwrap_or(ChannelModeKind::ParameterWhenSet);
+            let value = if kind == ChannelModeKind::Parameter {
+                Some(value)
+            } else {
+                None
+            };
+            RemovedChannelMode::new(mode, kind, value)
+        };
+
+        match (self.limit, target.limit) {
+            (Some(limit), None) => removed.push(remove_setting('l', limit.to_string())),
+            (current, Some(limit)) if current != Some(limit) => {
+                added.push(AddedChannelMode::Limit(limit))
+            }
+            _ => {}
+        }
+        for (&mode, value) in &self.settings {
+            if !target.settings.contains_key(&mode) {
+                removed.push(remove_setting(mode, value.clone()));
+            }
+        }
+        for (&mode, value) in &target.settings {
+            if self.settings.get(&mode) != Some(value) {
+                added.push(AddedChannelMode::new(
+                    mode,
+                    ChannelModeKind::ParameterWhenSet,
+                    Some(value.clone()),
+                ));
+            }
+        }
+
+        lists(
+            &self.bans,
+            &target.bans,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Ban,
+            AddedChannelMode::Ban,
+        );
+        lists(
+            &self.ban_exceptions,
+            &target.ban_exceptions,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::BanException,
+            AddedChannelMode::BanException,
+        );
+        lists(
+            &self.invite_exceptions,
+            &target.invite_exceptions,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::InviteException,
+            AddedChannelMode::InviteException,
+        );
+        lists(
+            &self.quiets,
+            &target.quiets,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Quiet,
+            AddedChannelMode::Quiet,
+        );
+        lists(
+            &self.ops,
+            &target.ops,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Op,
+            AddedChannelMode::Op,
+        );
+        lists(
+            &self.voices,
+            &target.voices,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Voice,
+            AddedChannelMode::Voice,
+        );
+        lists(
+            &self.halfops,
+            &target.halfops,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::HalfOp,
+            AddedChannelMode::HalfOp,
+        );
+        lists(
+            &self.admins,
+            &target.admins,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Admin,
+            AddedChannelMode::Admin,
+        );
+        lists(
+            &self.owners,
+            &target.owners,
+            &mut removed,
+            &mut added,
+            RemovedChannelMode::Owner,
+            AddedChannelMode::Owner,
+        );
+
+        for &(kind, current, target) in &[
+            (ChannelModeKind::List, &self.lists, &target.lists),
+            (ChannelModeKind::Prefix, &self.members, &target.members),
+        ] {
+            let empty = BTreeSet::new();
+            let modes = current.keys().chain(target.keys()).collect::<BTreeSet<_>>();
+            for &mode in modes {
+                let current = current.get(&mode).unwrap_or(&empty);
+                let target = target.get(&mode).unwrap_or(&empty);
+                for entry in current.difference(target) {
+                    removed.push(RemovedChannelMode::new(mode, kind, Some(entry.clone())));
+                }
+                for entry in target.difference(current) {
+                    added.push(AddedChannelMode::new(mode, kind, Some(entry.clone())));
+                }
+            }
+        }
+
+        removed
+            .into_iter()
+            .map(ChannelModeChange::Removed)
+            .chain(added.into_iter().map(ChannelModeChange::Added))
+            .collect()
+    }
+
+    /// Whether the flag with the given mode letter is set.
+    pub fn has_flag(&self, mode: char) -> bool {
+        self.flags.contains(&mode)
+    }
+
+    pub fn flags(&self) -> &BTreeSet<char> {
+        &self.flags
+    }
+
+    pub fn is_invite_only(&self) -> bool {
+        self.has_flag('i')
+    }
+
+    pub fn is_moderated(&self) -> bool {
+        self.has_flag('m')
+    }
+
+    pub fn is_no_external(&self) -> bool {
+        self.has_flag('n')
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.has_flag('p')
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.has_flag('s')
+    }
+
+    pub fn is_ops_topic(&self) -> bool {
+        self.has_flag('t')
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_ref().map(|key| &key[..])
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// The value of a setting such as the join throttle `j`, by its mode letter.
+    pub fn setting(&self, mode: char) -> Option<&str> {
+        self.settings.get(&mode).map(|value| &value[..])
+    }
+
+    pub fn bans(&self) -> &BTreeSet<String> {
+        &self.bans
+    }
+
+    pub fn ban_exceptions(&self) -> &BTreeSet<String> {
+        &self.ban_exceptions
+    }
+
+    pub fn invite_exceptions(&self) -> &BTreeSet<String> {
+        &self.invite_exceptions
+    }
+
+    pub fn quiets(&self) -> &BTreeSet<String> {
+        &self.quiets
+    }
+
+    pub fn ops(&self) -> &BTreeSet<String> {
+        &self.ops
+    }
+
+    pub fn voices(&self) -> &BTreeSet<String> {
+        &self.voices
+    }
+
+    pub fn halfops(&self) -> &BTreeSet<String> {
+        &self.halfops
+    }
+
+    pub fn admins(&self) -> &BTreeSet<String> {
+        &self.admins
+    }
+
+    pub fn owners(&self) -> &BTreeSet<String> {
+        &self.owners
+    }
+
+    /// The entries of a list mode without a variant of its own.
+    pub fn list(&self, mode: char) -> Option<&BTreeSet<String>> {
+        self.lists.get(&mode)
+    }
+
+    /// The nicknames given a membership mode without a variant of its own.
+    pub fn members(&self, mode: char) -> Option<&BTreeSet<String>> {
+        self.members.get(&mode)
+    }
+
+    pub fn is_op(&self, nick: &str) -> bool {
+        self.ops.contains(nick)
+    }
+
+    pub fn is_voiced(&self, nick: &str) -> bool {
+        self.voices.contains(nick)
+    }
+
+    pub fn is_halfop(&self, nick: &str) -> bool {
+        self.halfops.contains(nick)
+    }
+}
@@ -0,0 +1,113 @@
+use line::{tokens, Part};
+use {ChannelModeTable, Charset, ErrorKind, TextDecoder};
+
+/// Controls how closely incoming lines are held to the RFC 1459/2812 grammar.
+///
+/// The default is `strict`, except that runs of spaces between components are accepted as
+/// `parse_message` always has. Anything more lenient has to be asked for.
+#[derive(Clone, Debug)]
+pub struct ParserConfig {
+    /// Accept a bare `\n` as the line terminator as well as `\r\n`.
+    pub bare_lf: bool,
+    /// Accept runs of spaces between the components of a line instead of exactly one.
+    pub repeated_spaces: bool,
+    /// Accept spaces before the line terminator, as left behind by clients that send an empty
+    /// trailing parameter without its colon.
+    pub trailing_spaces: bool,
+    /// Drop stray NUL bytes from parameters instead of rejecting the line.
+    pub strip_nul: bool,
+    /// How parameters are decoded into text.
+    pub decoder: TextDecoder,
+    pub limits: Limits,
+    /// The channel modes of the server, which decide which mode letters take arguments.
+    pub channel_modes: ChannelModeTable,
+}
+
+impl ParserConfig {
+    /// Only accepts lines that follow the RFC grammar to the letter.
+    pub fn strict() -> ParserConfig {
+        ParserConfig {
+            bare_lf: false,
+            repeated_spaces: false,
+            trailing_spaces: false,
+            strip_nul: false,
+            decoder: TextDecoder::Utf8,
+            limits: Limits::default(),
+            channel_modes: ChannelModeTable::default(),
+        }
+    }
+
+    /// Accepts the deviations commonly seen from real servers, bouncers and clients.
+    pub fn lenient() -> ParserConfig {
+        ParserConfig {
+            bare_lf: true,
+            repeated_spaces: true,
+            trailing_spaces: true,
+            strip_nul: true,
+            decoder: TextDecoder::Utf8Fallback(Charset::Cp1252),
+            limits: Limits::default(),
+            channel_modes: ChannelModeTable::default(),
+        }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> ParserConfig {
+        ParserConfig {
+            repeated_spaces: true,
+            ..ParserConfig::strict()
+        }
+    }
+}
+
+/// Size limits on a single line. The defaults are those of RFC 2812 and the IRCv3 message-tags
+/// specification; raise them for servers that advertise larger values.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Limits {
+    /// Bytes in the tag section, including the leading `@` and the space after it.
+    pub tags: usize,
+    /// Bytes in the rest of the line, including the prefix and the line terminator.
+    pub body: usize,
+    /// Parameters following the verb.
+    pub params: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            tags: 8191,
+            body: 512,
+            params: 15,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks `line` against the limits, returning the offset at which one was exceeded.
+    ///
+    /// Only the lengths and the spaces between parameters are looked at, so this can be run on
+    /// partial and malformed lines before anything is allocated for them.
+    pub(crate) fn check(&self, line: &[u8]) -> Result<(), (usize, ErrorKind)> {
+        let mut in_body = false;
+        for token in tokens(line) {
+            match token.part {
+                // The space after the tags counts towards them.
+                Part::Tags if token.end + 1 > self.tags => return Err((0, ErrorKind::TagsTooLong)),
+                Part::Tags | Part::End => {}
+                // The body is everything after the tags.
+                Part::Prefix | Part::Verb if !in_body => {
+                    in_body = true;
+                    if line.len() - token.start > self.body {
+                        return Err((token.start + self.body, ErrorKind::BodyTooLong));
+                    }
+                }
+                Part::Prefix | Part::Verb => {}
+                Part::Param(idx) if idx == self.params => {
+                    return Err((token.start, ErrorKind::TooManyParams))
+                }
+                Part::Param(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::{self, Read};
+
+use {parse_message_with, ErrorKind, Message, ParseError, ParserConfig};
+
+const READ_SIZE: usize = 4096;
+
+/// Splits a stream of bytes into messages as complete lines arrive.
+#[derive(Debug, Default)]
+pub struct MessageDecoder {
+    buf: Vec<u8>,
+    config: ParserConfig,
+    // Set after reporting an overlong line, until the rest of it has been skipped.
+    discarding: bool,
+    // How far into `buf` we already know there to be no line terminator.
+    searched: usize,
+}
+
+impl MessageDecoder {
+    pub fn new() -> MessageDecoder {
+        MessageDecoder::default()
+    }
+
+    /// Creates a decoder that parses lines according to `config`.
+    ///
+    /// With `bare_lf` unset, a line only ends at `\r\n`.
+    pub fn with_config(config: ParserConfig) -> MessageDecoder {
+        MessageDecoder {
+            config,
+            ..MessageDecoder::default()
+        }
+    }
+
+    /// Appends bytes received from the peer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The bytes of the incomplete line at the end of the buffer. Complete lines that have not
+    /// been decoded yet are left out.
+    pub fn buffered(&self) -> &[u8] {
+        let start = if self.config.bare_lf {
+            self.buf
+                .iter()
+                .rposition(|c| *c == b'\n')
+                .map(|idx| idx + 1)
+        } else {
+            self.buf
+                .windows(2)
+                .rposition(|w| w == b"\r\n")
+                .map(|idx| idx + 2)
+        };
+        &self.buf[start.unwrap_or(0)..]
+    }
+
+    /// Returns the next buffered message, or `None` if no complete line has arrived yet.
+    ///
+    /// The message owns its data, as the line is removed from the buffer.
+    ///
+    /// A malformed line is returned as an error and dropped, so that the next call continues with
+    /// the line after it.
+    pub fn decode(&mut self) -> Option<Result<Message<'static>, ParseError>> {
+        loop {
+            let end = match self.line_end() {
+                Some(end) => end,
+                None => return self.check_partial(),
+            };
+            if self.discarding {
+                self.buf.drain(..end);
+                self.searched = 0;
+                self.discarding = false;
+                continue;
+            }
+            let result =
+                parse_message_with(&self.buf[..end], &self.config).map(Message::into_owned);
+            self.buf.drain(..end);
+            self.searched = 0;
+            return Some(result);
+        }
+    }
+
+    /// Reports a partial line as soon as it can no longer fit within the limits, rather than
+    /// buffering it until its end arrives.
+    fn check_partial(&mut self) -> Option<Result<Message<'static>, ParseError>> {
+        let limits = &self.config.limits;
+        if self.discarding {
+            self.buf.clear();
+            self.searched = 0;
+            return None;
+        }
+        if self.buf.len() <= limits.tags + limits.body {
+            return None;
+        }
+        let (offset, kind) = limits
+            .check(&self.buf)
+            .err()
+            .unwrap_or((self.buf.len(), ErrorKind::BodyTooLong));
+        let err = ParseError::at(&self.buf, offset, kind);
+        self.buf.clear();
+        self.searched = 0;
+        self.discarding = true;
+        Some(Err(err))
+    }
+
+    /// Reads from `reader` until a complete message is available.
+    ///
+    /// Returns `Ok(None)` once the reader is exhausted. Any incomplete line left in the buffer at
+    /// that point is reported as a parse error first.
+    pub fn read_message<R>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<Option<Result<Message<'static>, ParseError>>>
+    where
+        R: Read,
+    {
+        let mut chunk = [0; READ_SIZE];
+        loop {
+            if let Some(result) = self.decode() {
+                return Ok(Some(result));
+            }
+
+            match reader.read(&mut chunk) {
+                Ok(0) if self.buf.is_empty() => return Ok(None),
+                Ok(0) => {
+                    let result =
+                        parse_message_with(&self.buf, &self.config).map(Message::into_owned);
+                    self.buf.clear();
+                    self.searched = 0;
+                    return Ok(Some(result));
+                }
+                Ok(n) => self.feed(&chunk[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Finds the end of the first line in the buffer, including its terminator.
+    fn line_end(&mut self) -> Option<usize> {
+        if self.config.bare_lf {
+            return match self.buf[self.searched..].iter().position(|c| *c == b'\n') {
+                Some(idx) => Some(self.searched + idx + 1),
+                None => {
+                    self.searched = self.buf.len();
+                    None
+                }
+            };
+        }
+
+        // Back up one byte in case the last search stopped between a CR and its LF.
+        let start = self.searched.saturating_sub(1);
+        match self.buf[start..].windows(2).position(|w| w == b"\r\n") {
+            Some(idx) => Some(start + idx + 2),
+            None => {
+                self.searched = self.buf.len();
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::error::Error;
+use std::fmt;
+
+use nom;
+
+use line::{tokens, Part};
+
+/// The part of a message a parse error was found in.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Component {
+    Tags,
+    Prefix,
+    Verb,
+    /// The zero-based index of the parameter following the verb.
+    Parameter(usize),
+    ModeString,
+    Terminator,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Component::Tags => write!(f, "tags"),
+            Component::Prefix => write!(f, "prefix"),
+            Component::Verb => write!(f, "verb"),
+            Component::Parameter(idx) => write!(f, "parameter {}", idx),
+            Component::ModeString => write!(f, "mode string"),
+            Component::Terminator => write!(f, "line terminator"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ErrorKind {
+    /// The input ended before the line terminator.
+    Incomplete,
+    EmptyTags,
+    InvalidVerb,
+    MissingParameter,
+    UnexpectedParameter,
+    InvalidNumber,
+    MissingModeSign,
+    InvalidModeString,
+    /// A channel limit that is not a number.
+    InvalidLimit,
+    /// A channel key that is empty or contains a space or comma.
+    InvalidKey,
+    /// A ban, exception or invite mask that is not of the form `nick!user@host`.
+    InvalidMask,
+    /// Any other mode argument that is empty or contains a space or comma.
+    InvalidModeArgument,
+    /// A KICK with more than one channel, but not one for each user.
+    KickTargetMismatch,
+    InvalidTerminator,
+    /// Spaces were found before the line terminator and the parser is not configured to skip them.
+    TrailingSpace,
+    /// A NUL byte was found and the parser is not configured to strip it.
+    NulByte,
+    TrailingData,
+    InvalidSyntax,
+    /// The tag section is longer than the configured limit.
+    TagsTooLong,
+    /// The line after the tag section is longer than the configured limit.
+    BodyTooLong,
+    /// There are more parameters than the configured limit.
+    TooManyParams,
+}
+
+impl ErrorKind {
+    pub fn reason(&self) -> &'static str {
+        match *self {
+            ErrorKind::Incomplete => "line is not terminated",
+            ErrorKind::EmptyTags => "tag section is empty",
+            ErrorKind::InvalidVerb => "missing or malformed command",
+            ErrorKind::MissingParameter => "missing parameter",
+            ErrorKind::UnexpectedParameter => "unexpected parameter",
+            ErrorKind::InvalidNumber => "expected a number",
+            ErrorKind::MissingModeSign => "expected '+' or '-'",
+            ErrorKind::InvalidModeString => "malformed mode string",
+            ErrorKind::InvalidLimit => "channel limit is not a number",
+            ErrorKind::InvalidKey => "malformed channel key",
+            ErrorKind::InvalidMask => "malformed mask",
+            ErrorKind::InvalidModeArgument => "malformed mode argument",
+            ErrorKind::KickTargetMismatch => "expected one channel or one for each user",
+            ErrorKind::InvalidTerminator => "line must be terminated by CRLF",
+            ErrorKind::TrailingSpace => "unexpected space before the line terminator",
+            ErrorKind::NulByte => "unexpected NUL byte",
+            ErrorKind::TrailingData => "unexpected data after the line terminator",
+            ErrorKind::InvalidSyntax => "unexpected character",
+            ErrorKind::TagsTooLong => "tag section is too long",
+            ErrorKind::BodyTooLong => "line is too long",
+            ErrorKind::TooManyParams => "too many parameters",
+        }
+    }
+}
+
+impl ErrorKind {
+    /// The kinds that our own parsers report, passed through nom as custom error codes.
+    const CUSTOM: &'static [ErrorKind] = &[
+        ErrorKind::EmptyTags,
+        ErrorKind::MissingModeSign,
+        ErrorKind::InvalidModeString,
+        ErrorKind::InvalidLimit,
+        ErrorKind::InvalidKey,
+        ErrorKind::InvalidMask,
+        ErrorKind::InvalidModeArgument,
+        ErrorKind::KickTargetMismatch,
+    ];
+
+    pub(crate) fn code(self) -> nom::ErrorKind {
+        nom::ErrorKind::Custom(self as u32)
+    }
+
+    fn from_code(code: u32) -> Option<ErrorKind> {
+        ErrorKind::CUSTOM
+            .iter()
+            .cloned()
+            .find(|kind| *kind as u32 == code)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseError {
+    offset: usize,
+    component: Component,
+    kind: ErrorKind,
+}
+
+impl ParseError {
+    pub fn new(offset: usize, component: Component, kind: ErrorKind) -> ParseError {
+        ParseError {
+            offset,
+            component,
+            kind,
+        }
+    }
+
+    /// The byte offset into the line at which parsing failed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn component(&self) -> Component {
+        self.component
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// An error of the given kind in whichever component of `line` contains `offset`.
+    pub(crate) fn at(line: &[u8], offset: usize, kind: ErrorKind) -> ParseError {
+        let (component, _) = locate(line, offset);
+        ParseError::new(offset, component, kind)
+    }
+
+    pub(crate) fn from_nom(line: &[u8], err: nom::Err<&[u8]>) -> ParseError {
+        let (rest, code) = match err {
+            // Only a line without a terminator is incomplete. A parser that ran out of input in a
+            // terminated line was still expecting something where the terminator is.
+            nom::Err::Incomplete(_) => match line.iter().position(|c| *c == b'\n') {
+                Some(lf) => {
+                    let end = if lf > 0 && line[lf - 1] == b'\r' {
+                        lf - 1
+                    } else {
+                        lf
+                    };
+                    (&line[end..], nom::ErrorKind::Complete)
+                }
+                None => {
+                    return ParseError::new(
+                        line.len(),
+                        Component::Terminator,
+                        ErrorKind::Incomplete,
+                    )
+                }
+            },
+            nom::Err::Error(nom::Context::Code(rest, code))
+            | nom::Err::Failure(nom::Context::Code(rest, code)) => (rest, code),
+        };
+        let offset = line.len() - rest.len();
+        let (component, at_end) = locate(line, offset);
+
+        let kind = match (component, code) {
+            _ if line.get(offset) == Some(&0) => ErrorKind::NulByte,
+            (_, nom::ErrorKind::Custom(code)) => {
+                ErrorKind::from_code(code).unwrap_or(ErrorKind::InvalidSyntax)
+            }
+            (_, nom::ErrorKind::MapRes) => ErrorKind::InvalidNumber,
+            (Component::Verb, _) => ErrorKind::InvalidVerb,
+            (Component::Terminator, _) if line.get(offset) == Some(&b' ') => {
+                ErrorKind::TrailingSpace
+            }
+            (Component::Terminator, _) => ErrorKind::InvalidTerminator,
+            (_, nom::ErrorKind::Tag) if !at_end => ErrorKind::UnexpectedParameter,
+            (Component::ModeString, _) if !at_end => ErrorKind::InvalidModeString,
+            (_, _) if at_end => ErrorKind::MissingParameter,
+            (_, _) => ErrorKind::InvalidSyntax,
+        };
+        ParseError::new(offset, component, kind)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} at byte {}",
+            self.kind, self.component, self.offset
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Why a message could not be serialized.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SerializeError {
+    /// The output returned an error.
+    Format,
+    TagsTooLong,
+    BodyTooLong,
+    TooManyParams,
+}
+
+impl SerializeError {
+    pub(crate) fn from_kind(kind: ErrorKind) -> SerializeError {
+        match kind {
+            ErrorKind::TagsTooLong => SerializeError::TagsTooLong,
+            ErrorKind::BodyTooLong => SerializeError::BodyTooLong,
+            ErrorKind::TooManyParams => SerializeError::TooManyParams,
+            _ => SerializeError::Format,
+        }
+    }
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SerializeError::Format => write!(f, "error writing message"),
+            SerializeError::TagsTooLong => write!(f, "{}", ErrorKind::TagsTooLong),
+            SerializeError::BodyTooLong => write!(f, "{}", ErrorKind::BodyTooLong),
+            SerializeError::TooManyParams => write!(f, "{}", ErrorKind::TooManyParams),
+        }
+    }
+}
+
+impl Error for SerializeError {}
+
+impl From<fmt::Error> for SerializeError {
+    fn from(_: fmt::Error) -> SerializeError {
+        SerializeError::Format
+    }
+}
+
+/// Works out which component of `line` the byte at `offset` belongs to, and whether it lies past
+/// the last parameter. Spaces are counted as part of the component that follows them.
+fn locate(line: &[u8], offset: usize) -> (Component, bool) {
+    let mut is_mode = false;
+    let param = |idx, is_mode| {
+        if is_mode && idx > 0 {
+            Component::ModeString
+        } else {
+            Component::Parameter(idx)
+        }
+    };
+    let mut params = 0;
+    for token in tokens(line) {
+        let component = match token.part {
+            Part::Tags => Component::Tags,
+            Part::Prefix => Component::Prefix,
+            Part::Verb if token.start == token.end => return (Component::Verb, false),
+            Part::Verb => {
+                is_mode = line[token.start..token.end].eq_ignore_ascii_case(b"MODE");
+                Component::Verb
+            }
+            Part::Param(idx) => {
+                params = idx + 1;
+                param(idx, is_mode)
+            }
+            Part::End => {
+                return if token.start == line.len() {
+                    (param(params, is_mode), true)
+                } else if offset < token.start {
+                    // Spaces with nothing after them belong to the terminator.
+                    (Component::Terminator, false)
+                } else if offset == token.start && line[offset..].starts_with(b"\r\n") {
+                    // A well-formed terminator can only have been rejected for coming too early.
+                    (param(params, is_mode), true)
+                } else {
+                    (Component::Terminator, true)
+                };
+            }
+        };
+        if offset < token.end {
+            return (component, false);
+        }
+    }
+    // `tokens` always ends with the end of the line.
+    (Component::Terminator, true)
+}
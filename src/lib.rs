@@ -1,856 +1,1903 @@
 #[macro_use]
 extern crate nom;
 
+use std::borrow::Cow;
+
+mod channel;
+mod config;
+mod decoder;
+mod error;
+mod line;
 mod modes;
+mod reply;
 mod serialize;
+mod tags;
+mod text;
 
+pub use channel::ChannelModes;
+pub use config::{Limits, ParserConfig};
+pub use decoder::MessageDecoder;
+pub use error::{Component, ErrorKind, ParseError, SerializeError};
 use modes::channel_modes;
-pub use modes::{AddedChannelMode, ChannelModeChange, RemovedChannelMode};
-pub use serialize::Serialize;
-
-trait SplitToVec {
-    type Pattern;
+use modes::user_modes;
+pub use modes::{
+    parse_channel_modes, AddedChannelMode, ChannelModeChange, ChannelModeKind, ChannelModeTable,
+    ModeChanges, RemovedChannelMode, UserMode, UserModeChange,
+};
+pub use reply::Reply;
+pub use serialize::{format_channel_modes, Output, Serialize};
+use tags::tags;
+pub use tags::{Tag, Tags};
+pub use text::{Charset, Text, TextDecoder};
 
-    fn split_to_vec(&self, pattern: Self::Pattern) -> Vec<String>;
+trait SplitToVec<'a> {
+    fn split_to_vec(self, separator: u8, decoder: &TextDecoder) -> Vec<Text<'a>>;
 }
 
-impl<'a> SplitToVec for &'a [u8] {
-    type Pattern = &'a [u8];
-
-    fn split_to_vec(&self, pattern: &[u8]) -> Vec<String> {
-        self.split(|b| pattern.contains(b))
-            .map(|s| String::from_utf8_lossy(&s).into_owned())
+impl<'a> SplitToVec<'a> for &'a [u8] {
+    fn split_to_vec(self, separator: u8, decoder: &TextDecoder) -> Vec<Text<'a>> {
+        self.split(|b| *b == separator)
+            .map(|piece| Text::from_bytes(piece, decoder))
             .collect()
     }
 }
 
-impl SplitToVec for String {
-    type Pattern = &'static str;
-
-    fn split_to_vec(&self, pattern: &str) -> Vec<String> {
-        self.split(pattern).map(|s| s.to_owned()).collect()
+impl<'a> SplitToVec<'a> for Text<'a> {
+    fn split_to_vec(self, separator: u8, decoder: &TextDecoder) -> Vec<Text<'a>> {
+        self.split(separator, decoder)
     }
 }
 
-impl<T> SplitToVec for Option<T>
+impl<'a, T> SplitToVec<'a> for Option<T>
 where
-    T: SplitToVec + Clone,
+    T: SplitToVec<'a>,
 {
-    type Pattern = <T as SplitToVec>::Pattern;
-
-    fn split_to_vec(&self, pattern: Self::Pattern) -> Vec<String> {
-        self.clone()
-            .map(|inner| inner.split_to_vec(pattern))
+    fn split_to_vec(self, separator: u8, decoder: &TextDecoder) -> Vec<Text<'a>> {
+        self.map(|inner| inner.split_to_vec(separator, decoder))
             .unwrap_or_default()
     }
 }
 
-fn from_dec(input: String) -> Result<u8, std::num::ParseIntError> {
-    u8::from_str_radix(&input, 10)
+/// Converts a borrowed string into one that owns its data.
+fn owned(s: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(s.into_owned())
 }
 
-named!(
-    spaces<&[u8], &[u8]>,
-    is_a!(b" ")
-);
+fn owned_opt(s: Option<Cow<str>>) -> Option<Cow<'static, str>> {
+    s.map(owned)
+}
+
+fn owned_texts(v: Vec<Text>) -> Vec<Text<'static>> {
+    v.into_iter().map(Text::into_owned).collect()
+}
+
+fn from_dec(input: Text) -> Result<u8, std::num::ParseIntError> {
+    input.parse()
+}
+
+fn spaces<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], &'a [u8]> {
+    if cfg.repeated_spaces {
+        is_a!(input, b" ")
+    } else {
+        tag!(input, b" ")
+    }
+}
+
+fn line_end<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], &'a [u8]> {
+    let rest = if cfg.trailing_spaces {
+        try_parse!(input, opt!(is_a!(b" "))).0
+    } else {
+        input
+    };
+    let result = if cfg.bare_lf {
+        alt!(rest, tag!(b"\r\n") | tag!(b"\n"))
+    } else {
+        tag!(rest, b"\r\n")
+    };
+    // Report anything unexpected where it starts, not after the spaces skipped above.
+    result.map_err(|err| match err {
+        nom::Err::Error(_) => nom::Err::Error(nom::Context::Code(input, nom::ErrorKind::Tag)),
+        err => err,
+    })
+}
 
 #[derive(PartialEq, Eq, Debug)]
-pub struct Prefix(String);
+pub enum Prefix<'a> {
+    Server(Cow<'a, str>),
+    /// `nick[[!user]@host]`
+    User {
+        nick: Cow<'a, str>,
+        user: Option<Cow<'a, str>>,
+        host: Option<Cow<'a, str>>,
+    },
+}
+
+impl<'a> Prefix<'a> {
+    pub fn new_server<S>(name: S) -> Prefix<'a>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        Prefix::Server(name.into())
+    }
+
+    pub fn new_user<S>(
+        nick: S,
+        user: Option<Cow<'a, str>>,
+        host: Option<Cow<'a, str>>,
+    ) -> Prefix<'a>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        Prefix::User {
+            nick: nick.into(),
+            user,
+            host,
+        }
+    }
+
+    pub fn server_name(&self) -> Option<&str> {
+        match *self {
+            Prefix::Server(ref name) => Some(name),
+            Prefix::User { .. } => None,
+        }
+    }
+
+    pub fn nick(&self) -> Option<&str> {
+        match *self {
+            Prefix::Server(_) => None,
+            Prefix::User { ref nick, .. } => Some(nick),
+        }
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        match *self {
+            Prefix::Server(_) => None,
+            Prefix::User { ref user, .. } => user.as_deref(),
+        }
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        match *self {
+            Prefix::Server(_) => None,
+            Prefix::User { ref host, .. } => host.as_deref(),
+        }
+    }
+
+    /// Copies any borrowed data so that the prefix no longer refers to the input buffer.
+    pub fn into_owned(self) -> Prefix<'static> {
+        match self {
+            Prefix::Server(name) => Prefix::Server(owned(name)),
+            Prefix::User { nick, user, host } => Prefix::User {
+                nick: owned(nick),
+                user: owned_opt(user),
+                host: owned_opt(host),
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Prefix<'a> {
+    /// A bare name containing a dot is taken to be a server name, anything else a nickname.
+    fn from(prefix: &'a str) -> Prefix<'a> {
+        let (rest, host) = match prefix.find('@') {
+            Some(idx) => (&prefix[..idx], Some(Cow::Borrowed(&prefix[idx + 1..]))),
+            None => (prefix, None),
+        };
+        let (nick, user) = match rest.find('!') {
+            Some(idx) => (&rest[..idx], Some(Cow::Borrowed(&rest[idx + 1..]))),
+            None => (rest, None),
+        };
+
+        if user.is_none() && host.is_none() && nick.contains('.') {
+            Prefix::new_server(nick)
+        } else {
+            Prefix::User {
+                nick: Cow::Borrowed(nick),
+                user,
+                host,
+            }
+        }
+    }
+}
 
-fn prefix(input: &[u8]) -> nom::IResult<&[u8], Prefix> {
+fn prefix<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], Prefix<'a>> {
     let (rest, _) = try_parse!(input, tag!(":"));
-    let (rest, prefix) = try_parse!(rest, is_not!(" "));
-    let prefix = String::from_utf8_lossy(prefix).into_owned();
-    Ok((rest, Prefix(prefix)))
+    let (rest, prefix) = try_parse!(rest, is_not!(" \r\n"));
+    let prefix = match cfg.decoder.decode(prefix) {
+        Cow::Borrowed(prefix) => Prefix::from(prefix),
+        Cow::Owned(prefix) => Prefix::from(&*prefix).into_owned(),
+    };
+    Ok((rest, prefix))
 }
 
-named!(
-    argument_middle<&[u8], String>,
-    do_parse!(
-        peek!(verify!(nom::anychar, |val| val != ':')) >>
-        argument: is_not!(b" \0\r\n") >>
-        (String::from_utf8_lossy(argument).into_owned())
-    )
-);
+fn text<'a>(argument: &'a [u8], cfg: &ParserConfig) -> Text<'a> {
+    if argument.contains(&0) {
+        let stripped = argument
+            .iter()
+            .cloned()
+            .filter(|b| *b != 0)
+            .collect::<Vec<_>>();
+        Text::from_bytes(stripped, &cfg.decoder)
+    } else {
+        Text::from_bytes(argument, &cfg.decoder)
+    }
+}
 
-named!(
-    argument_trailing<&[u8], String>,
-    do_parse!(
-        tag!(b":") >>
-        argument: take_until_either!(b"\0\r\n") >>
-        (String::from_utf8_lossy(argument).into_owned())
-    )
-);
+fn argument_middle<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], Text<'a>> {
+    try_parse!(input, peek!(verify!(nom::anychar, |val| val != ':')));
+    let (rest, argument) = if cfg.strip_nul {
+        try_parse!(input, is_not!(b" \r\n"))
+    } else {
+        try_parse!(input, is_not!(b" \0\r\n"))
+    };
+    Ok((rest, text(argument, cfg)))
+}
+
+fn argument_trailing<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], Text<'a>> {
+    let (rest, _) = try_parse!(input, tag!(b":"));
+    let (rest, argument) = if cfg.strip_nul {
+        try_parse!(rest, take_until_either!(b"\r\n"))
+    } else {
+        try_parse!(rest, take_until_either!(b"\0\r\n"))
+    };
+    Ok((rest, text(argument, cfg)))
+}
 
-named!(
-    argument_maybe_last<&[u8], String>,
+named_args!(
+    argument_maybe_last<'a>(cfg: &ParserConfig)<&'a [u8], Text<'a>>,
     alt!(
-        argument_middle |
-        argument_trailing
+        call!(argument_middle, cfg) |
+        call!(argument_trailing, cfg)
     )
 );
 
-named!(
-    argument_middle_u8<&[u8], u8>,
+/// Parses any number of parameters, telling whether the last of them was a trailing one.
+fn arguments<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+) -> nom::IResult<&'a [u8], (Vec<Text<'a>>, bool)> {
+    let (rest, (mut params, last)) = try_parse!(
+        input,
+        do_parse!(
+            middle: many0!(preceded!(call!(spaces, cfg), call!(argument_middle, cfg))) >>
+            last: opt!(preceded!(call!(spaces, cfg), call!(argument_trailing, cfg))) >>
+            ((middle, last))
+        )
+    );
+    let trailing = last.is_some();
+    params.extend(last);
+    Ok((rest, (params, trailing)))
+}
+
+named_args!(
+    argument_middle_u8<'a>(cfg: &ParserConfig)<&'a [u8], u8>,
     do_parse!(
-        argument: map_res!(argument_middle, from_dec) >>
+        argument: map_res!(call!(argument_middle, cfg), from_dec) >>
         (argument)
     )
 );
 
-named!(
-    argument_trailing_u8<&[u8], u8>,
+named_args!(
+    argument_trailing_u8<'a>(cfg: &ParserConfig)<&'a [u8], u8>,
     do_parse!(
-        argument: map_res!(argument_trailing, from_dec) >>
+        argument: map_res!(call!(argument_trailing, cfg), from_dec) >>
         (argument)
     )
 );
 
-named!(
-    argument_maybe_last_u8<&[u8], u8>,
+named_args!(
+    argument_maybe_last_u8<'a>(cfg: &ParserConfig)<&'a [u8], u8>,
     alt!(
-        argument_middle_u8 |
-        argument_trailing_u8
+        call!(argument_middle_u8, cfg) |
+        call!(argument_trailing_u8, cfg)
     )
 );
 
 // Command parsers
 #[derive(PartialEq, Eq, Debug)]
-pub enum Command {
+pub enum Command<'a> {
     Pass {
-        password: String,
+        password: Text<'a>,
     },
     Nick {
-        nickname: String,
+        nickname: Text<'a>,
         hopcount: Option<u8>,
     },
     User {
-        username: String,
-        hostname: String,
-        servername: String,
-        realname: String,
+        username: Text<'a>,
+        hostname: Text<'a>,
+        servername: Text<'a>,
+        realname: Text<'a>,
     },
     Server {
-        servername: String,
+        servername: Text<'a>,
         hopcount: u8,
-        info: String,
+        info: Text<'a>,
     },
     Oper {
-        user: String,
-        password: String,
+        user: Text<'a>,
+        password: Text<'a>,
     },
     Quit {
-        message: Option<String>,
+        message: Option<Text<'a>>,
     },
     Squit {
-        server: String,
-        comment: String,
+        server: Text<'a>,
+        comment: Text<'a>,
     },
     Join {
-        channels: Vec<String>,
-        keys: Vec<String>,
+        channels: Vec<Text<'a>>,
+        keys: Vec<Text<'a>>,
     },
     Part {
-        channels: Vec<String>,
+        channels: Vec<Text<'a>>,
+        message: Option<Text<'a>>,
     },
+    /// `JOIN 0`, which leaves every channel the client is in.
+    PartAll,
+    /// MODE on a channel or a nickname, which are told apart by the prefix of the target.
     Mode {
-        target: String,
-        modechanges: Option<Vec<ChannelModeChange>>,
+        target: Text<'a>,
+        modechanges: ModeChanges,
     },
     Topic {
-        channel: String,
-        topic: Option<String>,
+        channel: Text<'a>,
+        topic: Option<Text<'a>>,
     },
     Names {
-        channels: Vec<String>,
+        channels: Vec<Text<'a>>,
     },
     List {
-        channels: Vec<String>,
-        server: Option<String>,
+        channels: Vec<Text<'a>>,
+        server: Option<Text<'a>>,
     },
     Invite {
-        nickname: String,
-        channel: String,
+        nickname: Text<'a>,
+        channel: Text<'a>,
     },
+    /// Either one channel for all of the users, or one channel per user, in order.
     Kick {
-        channel: String,
-        user: String,
-        comment: Option<String>,
+        channels: Vec<Text<'a>>,
+        users: Vec<Text<'a>>,
+        comment: Option<Text<'a>>,
     },
     Version {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Stats {
-        query: Option<String>,
-        server: Option<String>,
+        query: Option<Text<'a>>,
+        server: Option<Text<'a>>,
     },
     Links {
-        remote_server: Option<String>,
-        server_mask: Option<String>,
+        remote_server: Option<Text<'a>>,
+        server_mask: Option<Text<'a>>,
     },
     Time {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Connect {
-        target_server: String,
-        port: Option<String>,
-        remote_server: Option<String>,
+        target_server: Text<'a>,
+        port: Option<Text<'a>>,
+        remote_server: Option<Text<'a>>,
     },
     Trace {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Admin {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Info {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Privmsg {
-        receivers: Vec<String>,
-        message: String,
+        receivers: Vec<Text<'a>>,
+        message: Text<'a>,
     },
     Notice {
-        nickname: String,
-        text: String,
+        receivers: Vec<Text<'a>>,
+        text: Text<'a>,
     },
     Who {
-        name: Option<String>,
-        o: Option<String>,
+        name: Option<Text<'a>>,
+        o: Option<Text<'a>>,
     },
     Whois {
-        server: Option<String>,
-        nickmasks: Vec<String>,
+        server: Option<Text<'a>>,
+        nickmasks: Vec<Text<'a>>,
     },
     Whowas {
-        nickname: String,
-        count: Option<String>,
-        server: Option<String>,
+        nickname: Text<'a>,
+        count: Option<Text<'a>>,
+        server: Option<Text<'a>>,
     },
     Kill {
-        nickname: String,
-        comment: String,
+        nickname: Text<'a>,
+        comment: Text<'a>,
     },
     Ping {
-        server1: String,
-        server2: Option<String>,
+        server1: Text<'a>,
+        server2: Option<Text<'a>>,
     },
     Pong {
-        daemon1: String,
-        daemon2: Option<String>,
+        daemon1: Text<'a>,
+        daemon2: Option<Text<'a>>,
     },
     Error {
-        message: String,
+        message: Text<'a>,
     },
     Away {
-        message: Option<String>,
+        message: Option<Text<'a>>,
     },
     Rehash,
     Restart,
     Summon {
-        user: String,
-        server: Option<String>,
+        user: Text<'a>,
+        server: Option<Text<'a>>,
     },
     Users {
-        server: Option<String>,
+        server: Option<Text<'a>>,
     },
     Wallops {
-        text: String,
+        text: Text<'a>,
     },
     Userhost {
-        nicknames: Vec<String>,
+        nicknames: Vec<Text<'a>>,
     },
     Ison {
-        nicknames: Vec<String>,
+        nicknames: Vec<Text<'a>>,
+    },
+    Motd {
+        target: Option<Text<'a>>,
+    },
+    Lusers {
+        mask: Option<Text<'a>>,
+        target: Option<Text<'a>>,
+    },
+    /// Registers a service. The two reserved parameters are not kept, and are sent as `*`
+    /// and `0`.
+    Service {
+        nickname: Text<'a>,
+        distribution: Text<'a>,
+        service_type: Text<'a>,
+        info: Text<'a>,
     },
+    Squery {
+        servicename: Text<'a>,
+        text: Text<'a>,
+    },
+    Servlist {
+        mask: Option<Text<'a>>,
+        service_type: Option<Text<'a>>,
+    },
+    Die,
+    Numeric {
+        code: Reply,
+        params: Vec<Text<'a>>,
+        /// Whether the last parameter is sent after a colon, as for `Raw`.
+        trailing: bool,
+    },
+    /// Any command that is not otherwise recognised.
+    Raw {
+        verb: Cow<'a, str>,
+        params: Vec<Text<'a>>,
+        /// Whether the last parameter is sent after a colon even where it does not need one, as
+        /// it was received.
+        trailing: bool,
+    },
+}
+
+impl<'a> Command<'a> {
+    /// Builds a command from its verb and parameters, parsing it exactly as if it had been
+    /// received in a line of its own with `ParserConfig::strict()`.
+    ///
+    /// Error offsets refer to that line, `VERB param... :last`.
+    pub fn from_parts(verb: &str, params: &[Text]) -> Result<Command<'static>, ParseError> {
+        if !is_verb(verb.as_bytes()) {
+            return Err(ParseError::new(0, Component::Verb, ErrorKind::InvalidVerb));
+        }
+
+        let mut offset = verb.len();
+        for (idx, param) in params.iter().enumerate() {
+            offset += 1;
+            let last = idx + 1 == params.len();
+            let bad_byte = param
+                .as_bytes()
+                .iter()
+                .position(|b| b"\0\r\n".contains(b) || (!last && *b == b' '));
+            if let Some(pos) = bad_byte {
+                let kind = if param.as_bytes()[pos] == 0 {
+                    ErrorKind::NulByte
+                } else {
+                    ErrorKind::InvalidSyntax
+                };
+                return Err(ParseError::new(
+                    offset + pos,
+                    Component::Parameter(idx),
+                    kind,
+                ));
+            }
+            if !last && (param.is_empty() || param.starts_with(':')) {
+                return Err(ParseError::new(
+                    offset,
+                    Component::Parameter(idx),
+                    ErrorKind::InvalidSyntax,
+                ));
+            }
+            offset += param.as_bytes().len();
+        }
+
+        let mut line = Command::Raw {
+            verb: Cow::Borrowed(verb),
+            params: params.iter().map(Text::borrow).collect(),
+            trailing: false,
+        }
+        .to_bytes()
+        .map_err(|_| ParseError::new(0, Component::Verb, ErrorKind::InvalidVerb))?;
+        line.extend_from_slice(b"\r\n");
+        let message = parse_message_with(&line, &ParserConfig::strict())?;
+        Ok(message.command.into_owned())
+    }
+
+    /// The verb as it is sent on the wire.
+    pub fn verb(&self) -> Cow<'_, str> {
+        let verb = match *self {
+            Command::Pass { .. } => "PASS",
+            Command::Nick { .. } => "NICK",
+            Command::User { .. } => "USER",
+            Command::Server { .. } => "SERVER",
+            Command::Oper { .. } => "OPER",
+            Command::Quit { .. } => "QUIT",
+            Command::Squit { .. } => "SQUIT",
+            Command::Join { .. } => "JOIN",
+            Command::Part { .. } => "PART",
+            Command::PartAll => "JOIN",
+            Command::Mode { .. } => "MODE",
+            Command::Topic { .. } => "TOPIC",
+            Command::Names { .. } => "NAMES",
+            Command::List { .. } => "LIST",
+            Command::Invite { .. } => "INVITE",
+            Command::Kick { .. } => "KICK",
+            Command::Version { .. } => "VERSION",
+            Command::Stats { .. } => "STATS",
+            Command::Links { .. } => "LINKS",
+            Command::Time { .. } => "TIME",
+            Command::Connect { .. } => "CONNECT",
+            Command::Trace { .. } => "TRACE",
+            Command::Admin { .. } => "ADMIN",
+            Command::Info { .. } => "INFO",
+            Command::Privmsg { .. } => "PRIVMSG",
+            Command::Notice { .. } => "NOTICE",
+            Command::Who { .. } => "WHO",
+            Command::Whois { .. } => "WHOIS",
+            Command::Whowas { .. } => "WHOWAS",
+            Command::Kill { .. } => "KILL",
+            Command::Ping { .. } => "PING",
+            Command::Pong { .. } => "PONG",
+            Command::Error { .. } => "ERROR",
+            Command::Away { .. } => "AWAY",
+            Command::Rehash => "REHASH",
+            Command::Restart => "RESTART",
+            Command::Summon { .. } => "SUMMON",
+            Command::Users { .. } => "USERS",
+            Command::Wallops { .. } => "WALLOPS",
+            Command::Userhost { .. } => "USERHOST",
+            Command::Ison { .. } => "ISON",
+            Command::Motd { .. } => "MOTD",
+            Command::Lusers { .. } => "LUSERS",
+            Command::Service { .. } => "SERVICE",
+            Command::Squery { .. } => "SQUERY",
+            Command::Servlist { .. } => "SERVLIST",
+            Command::Die => "DIE",
+            Command::Numeric { code, .. } => return Cow::Owned(format!("{:03}", code.code())),
+            Command::Raw { ref verb, .. } if verb.bytes().any(|b| b.is_ascii_lowercase()) => {
+                return Cow::Owned(verb.to_ascii_uppercase())
+            }
+            Command::Raw { ref verb, .. } => verb,
+        };
+        Cow::Borrowed(verb)
+    }
+
+    /// The parameters as they are sent on the wire, in order.
+    ///
+    /// Lists such as the channels of a JOIN are joined into a single parameter, and a mode
+    /// string and its arguments are separate parameters.
+    pub fn params(&self) -> Vec<Text<'_>> {
+        fn opt<'b>(params: &mut Vec<Text<'b>>, param: &'b Option<Text>) {
+            if let Some(ref param) = *param {
+                params.push(param.borrow());
+            }
+        }
+        fn list<'b>(params: &mut Vec<Text<'b>>, items: &[Text]) {
+            if !items.is_empty() {
+                params.push(Text::join(items, ','));
+            }
+        }
+
+        let mut params = Vec::new();
+        match *self {
+            Command::Pass { ref password } => params.push(password.borrow()),
+            Command::Nick {
+                ref nickname,
+                hopcount,
+            } => {
+                params.push(nickname.borrow());
+                if let Some(hopcount) = hopcount {
+                    params.push(hopcount.to_string().into());
+                }
+            }
+            Command::User {
+                ref username,
+                ref hostname,
+                ref servername,
+                ref realname,
+            } => {
+                params.push(username.borrow());
+                params.push(hostname.borrow());
+                params.push(servername.borrow());
+                params.push(realname.borrow());
+            }
+            Command::Server {
+                ref servername,
+                hopcount,
+                ref info,
+            } => {
+                params.push(servername.borrow());
+                params.push(hopcount.to_string().into());
+                params.push(info.borrow());
+            }
+            Command::Oper {
+                ref user,
+                ref password,
+            } => {
+                params.push(user.borrow());
+                params.push(password.borrow());
+            }
+            Command::Quit { ref message } => opt(&mut params, message),
+            Command::Squit {
+                ref server,
+                ref comment,
+            } => {
+                params.push(server.borrow());
+                params.push(comment.borrow());
+            }
+            Command::Join {
+                ref channels,
+                ref keys,
+            } => {
+                params.push(Text::join(channels, ','));
+                list(&mut params, keys);
+            }
+            Command::Part {
+                ref channels,
+                ref message,
+            } => {
+                params.push(Text::join(channels, ','));
+                opt(&mut params, message);
+            }
+            Command::PartAll => params.push("0".into()),
+            Command::Mode {
+                ref target,
+                ref modechanges,
+            } => {
+                params.push(target.borrow());
+                let mut modes = String::new();
+                // Writing to a String cannot fail.
+                let _ = modechanges.serialize(&mut modes);
+                params.extend(modes.split_whitespace().map(|p| p.to_owned().into()));
+            }
+            Command::Topic {
+                ref channel,
+                ref topic,
+            } => {
+                params.push(channel.borrow());
+                opt(&mut params, topic);
+            }
+            Command::Names { ref channels } => list(&mut params, channels),
+            Command::List {
+                ref channels,
+                ref server,
+            } => {
+                list(&mut params, channels);
+                opt(&mut params, server);
+            }
+            Command::Invite {
+                ref nickname,
+                ref channel,
+            } => {
+                params.push(nickname.borrow());
+                params.push(channel.borrow());
+            }
+            Command::Kick {
+                ref channels,
+                ref users,
+                ref comment,
+            } => {
+                params.push(Text::join(channels, ','));
+                params.push(Text::join(users, ','));
+                opt(&mut params, comment);
+            }
+            Command::Version { ref server }
+            | Command::Time { ref server }
+            | Command::Trace { ref server }
+            | Command::Admin { ref server }
+            | Command::Info { ref server }
+            | Command::Users { ref server }
+            | Command::Motd { target: ref server } => opt(&mut params, server),
+            Command::Stats {
+                ref query,
+                ref server,
+            } => {
+                opt(&mut params, query);
+                opt(&mut params, server);
+            }
+            Command::Links {
+                ref remote_server,
+                ref server_mask,
+            } => {
+                opt(&mut params, remote_server);
+                opt(&mut params, server_mask);
+            }
+            Command::Connect {
+                ref target_server,
+                ref port,
+                ref remote_server,
+            } => {
+                params.push(target_server.borrow());
+                opt(&mut params, port);
+                opt(&mut params, remote_server);
+            }
+            Command::Privmsg {
+                ref receivers,
+                ref message,
+            } => {
+                params.push(Text::join(receivers, ','));
+                params.push(message.borrow());
+            }
+            Command::Notice {
+                ref receivers,
+                ref text,
+            } => {
+                params.push(Text::join(receivers, ','));
+                params.push(text.borrow());
+            }
+            Command::Who { ref name, ref o } => {
+                opt(&mut params, name);
+                opt(&mut params, o);
+            }
+            Command::Whois {
+                ref server,
+                ref nickmasks,
+            } => {
+                opt(&mut params, server);
+                params.push(Text::join(nickmasks, ','));
+            }
+            Command::Whowas {
+                ref nickname,
+                ref count,
+                ref server,
+            } => {
+                params.push(nickname.borrow());
+                opt(&mut params, count);
+                opt(&mut params, server);
+            }
+            Command::Kill {
+                ref nickname,
+                ref comment,
+            } => {
+                params.push(nickname.borrow());
+                params.push(comment.borrow());
+            }
+            Command::Ping {
+                ref server1,
+                ref server2,
+            } => {
+                params.push(server1.borrow());
+                opt(&mut params, server2);
+            }
+            Command::Pong {
+                ref daemon1,
+                ref daemon2,
+            } => {
+                params.push(daemon1.borrow());
+                opt(&mut params, daemon2);
+            }
+            Command::Error { ref message } => params.push(message.borrow()),
+            Command::Away { ref message } => opt(&mut params, message),
+            Command::Rehash | Command::Restart | Command::Die => {}
+            Command::Summon {
+                ref user,
+                ref server,
+            } => {
+                params.push(user.borrow());
+                opt(&mut params, server);
+            }
+            Command::Wallops { ref text } => params.push(text.borrow()),
+            Command::Userhost { ref nicknames } | Command::Ison { ref nicknames } => {
+                params.extend(nicknames.iter().map(Text::borrow))
+            }
+            Command::Lusers {
+                ref mask,
+                ref target,
+            } => {
+                opt(&mut params, mask);
+                opt(&mut params, target);
+            }
+            Command::Service {
+                ref nickname,
+                ref distribution,
+                ref service_type,
+                ref info,
+            } => {
+                params.push(nickname.borrow());
+                params.push("*".into());
+                params.push(distribution.borrow());
+                params.push(service_type.borrow());
+                params.push("0".into());
+                params.push(info.borrow());
+            }
+            Command::Squery {
+                ref servicename,
+                ref text,
+            } => {
+                params.push(servicename.borrow());
+                params.push(text.borrow());
+            }
+            Command::Servlist {
+                ref mask,
+                ref service_type,
+            } => {
+                opt(&mut params, mask);
+                opt(&mut params, service_type);
+            }
+            Command::Numeric { params: ref ps, .. } | Command::Raw { params: ref ps, .. } => {
+                params.extend(ps.iter().map(Text::borrow))
+            }
+        }
+        params
+    }
+
+    /// Copies any borrowed data so that the command no longer refers to the input buffer.
+    pub fn into_owned(self) -> Command<'static> {
+        match self {
+            Command::Pass { password } => Command::Pass {
+                password: password.into_owned(),
+            },
+            Command::Nick { nickname, hopcount } => Command::Nick {
+                nickname: nickname.into_owned(),
+                hopcount,
+            },
+            Command::User {
+                username,
+                hostname,
+                servername,
+                realname,
+            } => Command::User {
+                username: username.into_owned(),
+                hostname: hostname.into_owned(),
+                servername: servername.into_owned(),
+                realname: realname.into_owned(),
+            },
+            Command::Server {
+                servername,
+                hopcount,
+                info,
+            } => Command::Server {
+                servername: servername.into_owned(),
+                hopcount,
+                info: info.into_owned(),
+            },
+            Command::Oper { user, password } => Command::Oper {
+                user: user.into_owned(),
+                password: password.into_owned(),
+            },
+            Command::Quit { message } => Command::Quit {
+                message: message.map(Text::into_owned),
+            },
+            Command::Squit { server, comment } => Command::Squit {
+                server: server.into_owned(),
+                comment: comment.into_owned(),
+            },
+            Command::Join { channels, keys } => Command::Join {
+                channels: owned_texts(channels),
+                keys: owned_texts(keys),
+            },
+            Command::Part { channels, message } => Command::Part {
+                channels: owned_texts(channels),
+                message: message.map(Text::into_owned),
+            },
+            Command::PartAll => Command::PartAll,
+            Command::Mode {
+                target,
+                modechanges,
+            } => Command::Mode {
+                target: target.into_owned(),
+                modechanges,
+            },
+            Command::Topic { channel, topic } => Command::Topic {
+                channel: channel.into_owned(),
+                topic: topic.map(Text::into_owned),
+            },
+            Command::Names { channels } => Command::Names {
+                channels: owned_texts(channels),
+            },
+            Command::List { channels, server } => Command::List {
+                channels: owned_texts(channels),
+                server: server.map(Text::into_owned),
+            },
+            Command::Invite { nickname, channel } => Command::Invite {
+                nickname: nickname.into_owned(),
+                channel: channel.into_owned(),
+            },
+            Command::Kick {
+                channels,
+                users,
+                comment,
+            } => Command::Kick {
+                channels: owned_texts(channels),
+                users: owned_texts(users),
+                comment: comment.map(Text::into_owned),
+            },
+            Command::Version { server } => Command::Version {
+                server: server.map(Text::into_owned),
+            },
+            Command::Stats { query, server } => Command::Stats {
+                query: query.map(Text::into_owned),
+                server: server.map(Text::into_owned),
+            },
+            Command::Links {
+                remote_server,
+                server_mask,
+            } => Command::Links {
+                remote_server: remote_server.map(Text::into_owned),
+                server_mask: server_mask.map(Text::into_owned),
+            },
+            Command::Time { server } => Command::Time {
+                server: server.map(Text::into_owned),
+            },
+            Command::Connect {
+                target_server,
+                port,
+                remote_server,
+            } => Command::Connect {
+                target_server: target_server.into_owned(),
+                port: port.map(Text::into_owned),
+                remote_server: remote_server.map(Text::into_owned),
+            },
+            Command::Trace { server } => Command::Trace {
+                server: server.map(Text::into_owned),
+            },
+            Command::Admin { server } => Command::Admin {
+                server: server.map(Text::into_owned),
+            },
+            Command::Info { server } => Command::Info {
+                server: server.map(Text::into_owned),
+            },
+            Command::Privmsg { receivers, message } => Command::Privmsg {
+                receivers: owned_texts(receivers),
+                message: message.into_owned(),
+            },
+            Command::Notice { receivers, text } => Command::Notice {
+                receivers: owned_texts(receivers),
+                text: text.into_owned(),
+            },
+            Command::Who { name, o } => Command::Who {
+                name: name.map(Text::into_owned),
+                o: o.map(Text::into_owned),
+            },
+            Command::Whois { server, nickmasks } => Command::Whois {
+                server: server.map(Text::into_owned),
+                nickmasks: owned_texts(nickmasks),
+            },
+            Command::Whowas {
+                nickname,
+                count,
+                server,
+            } => Command::Whowas {
+                nickname: nickname.into_owned(),
+                count: count.map(Text::into_owned),
+                server: server.map(Text::into_owned),
+            },
+            Command::Kill { nickname, comment } => Command::Kill {
+                nickname: nickname.into_owned(),
+                comment: comment.into_owned(),
+            },
+            Command::Ping { server1, server2 } => Command::Ping {
+                server1: server1.into_owned(),
+                server2: server2.map(Text::into_owned),
+            },
+            Command::Pong { daemon1, daemon2 } => Command::Pong {
+                daemon1: daemon1.into_owned(),
+                daemon2: daemon2.map(Text::into_owned),
+            },
+            Command::Error { message } => Command::Error {
+                message: message.into_owned(),
+            },
+            Command::Away { message } => Command::Away {
+                message: message.map(Text::into_owned),
+            },
+            Command::Rehash => Command::Rehash,
+            Command::Restart => Command::Restart,
+            Command::Summon { user, server } => Command::Summon {
+                user: user.into_owned(),
+                server: server.map(Text::into_owned),
+            },
+            Command::Users { server } => Command::Users {
+                server: server.map(Text::into_owned),
+            },
+            Command::Wallops { text } => Command::Wallops {
+                text: text.into_owned(),
+            },
+            Command::Userhost { nicknames } => Command::Userhost {
+                nicknames: owned_texts(nicknames),
+            },
+            Command::Ison { nicknames } => Command::Ison {
+                nicknames: owned_texts(nicknames),
+            },
+            Command::Motd { target } => Command::Motd {
+                target: target.map(Text::into_owned),
+            },
+            Command::Lusers { mask, target } => Command::Lusers {
+                mask: mask.map(Text::into_owned),
+                target: target.map(Text::into_owned),
+            },
+            Command::Service {
+                nickname,
+                distribution,
+                service_type,
+                info,
+            } => Command::Service {
+                nickname: nickname.into_owned(),
+                distribution: distribution.into_owned(),
+                service_type: service_type.into_owned(),
+                info: info.into_owned(),
+            },
+            Command::Squery { servicename, text } => Command::Squery {
+                servicename: servicename.into_owned(),
+                text: text.into_owned(),
+            },
+            Command::Servlist { mask, service_type } => Command::Servlist {
+                mask: mask.map(Text::into_owned),
+                service_type: service_type.map(Text::into_owned),
+            },
+            Command::Die => Command::Die,
+            Command::Numeric {
+                code,
+                params,
+                trailing,
+            } => Command::Numeric {
+                code,
+                params: owned_texts(params),
+                trailing,
+            },
+            Command::Raw {
+                verb,
+                params,
+                trailing,
+            } => Command::Raw {
+                verb: owned(verb),
+                params: owned_texts(params),
+                trailing,
+            },
+        }
+    }
 }
 
-named!(
-    command_pass<&[u8], Command>,
+named_args!(
+    command_pass<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"PASS") >>
+        call!(spaces, cfg) >>
+        password: call!(argument_maybe_last, cfg) >>
+        (Command::Pass { password })
+    )
+);
+
+named_args!(
+    command_nick<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"PASS") >>
-        spaces >>
-        password: argument_maybe_last >>
-        (Command::Pass { password: password.to_string() })
+        tag_no_case!(b"NICK") >>
+        call!(spaces, cfg) >>
+        nickname: call!(argument_maybe_last, cfg) >>
+        hopcount: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last_u8, cfg))) >>
+        (Command::Nick { nickname, hopcount })
     )
 );
 
-named!(
-    command_nick<&[u8], Command>,
+named_args!(
+    command_user<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"NICK") >>
-        spaces >>
-        nickname: argument_maybe_last >>
-        opt!(spaces) >>
-        hopcount: opt!(argument_maybe_last_u8) >>
-        (Command::Nick { nickname: nickname.to_string(), hopcount: hopcount })
+        tag_no_case!(b"USER") >>
+        call!(spaces, cfg) >>
+        username: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        hostname: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        servername: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        realname: call!(argument_maybe_last, cfg) >>
+        (Command::User { username, hostname, servername, realname })
     )
 );
 
-named!(
-    command_user<&[u8], Command>,
+named_args!(
+    command_server<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"USER") >>
-        spaces >>
-        username: argument_middle >>
-        spaces >>
-        hostname: argument_middle >>
-        spaces >>
-        servername: argument_middle >>
-        spaces >>
-        realname: argument_maybe_last >>
-        (Command::User { username: username.to_string(),
-                         hostname: hostname.to_string(),
-                         servername: servername.to_string(),
-                         realname: realname.to_string() })
+        tag_no_case!(b"SERVER") >>
+        call!(spaces, cfg) >>
+        servername: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        hopcount: call!(argument_middle_u8, cfg) >>
+        call!(spaces, cfg) >>
+        info: call!(argument_maybe_last, cfg) >>
+        (Command::Server { servername, hopcount, info })
     )
 );
 
-named!(
-    command_server<&[u8], Command>,
+named_args!(
+    command_oper<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"SERVER") >>
-        spaces >>
-        servername: argument_middle >>
-        spaces >>
-        hopcount: argument_middle_u8 >>
-        spaces >>
-        info: argument_maybe_last >>
-        (Command::Server { servername: servername.to_string(), hopcount: hopcount, info: info.to_string() })
+        tag_no_case!(b"OPER") >>
+        call!(spaces, cfg) >>
+        user: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        password: call!(argument_maybe_last, cfg) >>
+        (Command::Oper { user, password })
     )
 );
 
-named!(
-    command_oper<&[u8], Command>,
+named_args!(
+    command_quit<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"OPER") >>
-        spaces >>
-        user: argument_middle >>
-        spaces >>
-        password: argument_maybe_last >>
-        (Command::Oper { user: user.to_string(), password: password.to_string() })
+        tag_no_case!(b"QUIT") >>
+        message: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Quit { message })
     )
 );
 
-named!(
-    command_quit<&[u8], Command>,
+named_args!(
+    command_squit<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"QUIT") >>
-        opt!(spaces) >>
-        message: opt!(argument_maybe_last) >>
-        (Command::Quit { message: message.map(|m| m.to_string()) })
+        tag_no_case!(b"SQUIT") >>
+        call!(spaces, cfg) >>
+        server: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        comment: call!(argument_maybe_last, cfg) >>
+        (Command::Squit { server, comment })
     )
 );
 
-named!(
-    command_squit<&[u8], Command>,
+named_args!(
+    command_join<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"SQUIT") >>
-        spaces >>
-        server: argument_middle >>
-        spaces >>
-        comment: argument_maybe_last >>
-        (Command::Squit { server: server.to_string(), comment: comment.to_string() })
+        tag_no_case!(b"JOIN") >>
+        call!(spaces, cfg) >>
+        channels: call!(argument_maybe_last, cfg) >>
+        keys: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (join(channels, keys, cfg))
     )
 );
 
-named!(
-    command_join<&[u8], Command>,
+/// Builds a JOIN, treating a lone `0` as a request to leave every channel. Servers ignore any
+/// keys given with it.
+fn join<'a>(channels: Text<'a>, keys: Option<Text<'a>>, cfg: &ParserConfig) -> Command<'a> {
+    if channels.as_bytes() == b"0" {
+        return Command::PartAll;
+    }
+    Command::Join {
+        channels: channels.split_to_vec(b',', &cfg.decoder),
+        keys: keys.split_to_vec(b',', &cfg.decoder),
+    }
+}
+
+named_args!(
+    command_part<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"JOIN") >>
-        spaces >>
-        channels: argument_maybe_last >>
-        opt!(spaces) >>
-        keys: opt!(argument_maybe_last) >>
-        (Command::Join { channels: channels.split_to_vec(","),
-                         keys: keys.split_to_vec(",") })
+        tag_no_case!(b"PART") >>
+        call!(spaces, cfg) >>
+        channels: call!(argument_maybe_last, cfg) >>
+        message: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Part { channels: channels.split_to_vec(b',', &cfg.decoder), message })
     )
 );
 
-named!(
-    command_part<&[u8], Command>,
+/// Whether a MODE target names a channel rather than a nickname.
+fn is_channel(target: &[u8]) -> bool {
+    target.first().is_some_and(|c| b"#&+!".contains(c))
+}
+
+// An empty trailing parameter, such as the one some servers end a MODE command with.
+named_args!(empty_trailing<'a>(cfg: &ParserConfig)<&'a [u8], ()>,
     do_parse!(
-        tag!(b"PART") >>
-        spaces >>
-        channels: argument_maybe_last >>
-        (Command::Part { channels: channels.split_to_vec(",") })
+        call!(spaces, cfg) >>
+        tag!(":") >>
+        peek!(one_of!(b"\r\n")) >>
+        ()
     )
 );
 
-named!(channel_mode<&[u8], Command>,
+named_args!(channel_mode<'a>(cfg: &ParserConfig, target: Text<'a>)<&'a [u8], Command<'a>>,
     do_parse!(
-        target: argument_maybe_last >>
-        spaces >>
-        modechanges: opt!(channel_modes) >>
-        (Command::Mode { target: target,
-                         modechanges: modechanges })
+        modechanges: opt!(
+            do_parse!(
+                call!(spaces, cfg) >>
+                // Servers often send the mode string as the trailing parameter.
+                opt!(tag!(":")) >>
+                peek!(none_of!(b"\0\r\n")) >>
+                modechanges: return_error!(call!(channel_modes, cfg)) >>
+                (modechanges)
+            )
+        ) >>
+        opt!(call!(empty_trailing, cfg)) >>
+        (Command::Mode { target, modechanges: ModeChanges::Channel(modechanges) })
     )
 );
 
-named!(
-    command_mode<&[u8], Command>,
+named_args!(user_mode<'a>(cfg: &ParserConfig, target: Text<'a>)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"MODE") >>
-        spaces >>
-        modes: channel_mode >>
-        (modes)
+        modechanges: opt!(
+            do_parse!(
+                call!(spaces, cfg) >>
+                // Servers often send the mode string as the trailing parameter.
+                opt!(tag!(":")) >>
+                peek!(none_of!(b"\0\r\n")) >>
+                modechanges: return_error!(call!(user_modes, cfg)) >>
+                (modechanges)
+            )
+        ) >>
+        opt!(call!(empty_trailing, cfg)) >>
+        (Command::Mode { target, modechanges: ModeChanges::User(modechanges) })
     )
 );
 
-named!(
-    command_topic<&[u8], Command>,
+fn command_mode<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], Command<'a>> {
+    let (rest, target) = try_parse!(
+        input,
+        do_parse!(
+            tag_no_case!(b"MODE") >>
+            call!(spaces, cfg) >>
+            target: call!(argument_maybe_last, cfg) >>
+            (target)
+        )
+    );
+    if is_channel(target.as_bytes()) {
+        channel_mode(rest, cfg, target)
+    } else {
+        user_mode(rest, cfg, target)
+    }
+}
+
+named_args!(
+    command_topic<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"TOPIC") >>
-        spaces >>
-        channel: argument_middle >>
-        opt!(spaces) >>
-        topic: opt!(argument_maybe_last) >>
-        (Command::Topic { channel: channel.to_string(), topic: topic.map(|t| t.to_string()) })
+        tag_no_case!(b"TOPIC") >>
+        call!(spaces, cfg) >>
+        channel: call!(argument_middle, cfg) >>
+        topic: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Topic { channel, topic })
     )
 );
 
-named!(
-    command_names<&[u8], Command>,
+named_args!(
+    command_names<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"NAMES") >>
-        opt!(spaces) >>
-        channels: opt!(argument_maybe_last) >>
-        (Command::Names { channels: channels.split_to_vec(",") })
+        tag_no_case!(b"NAMES") >>
+        channels: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Names { channels: channels.split_to_vec(b',', &cfg.decoder) })
     )
 );
 
-named!(
-    command_list<&[u8], Command>,
+named_args!(
+    command_list<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"LIST") >>
-        opt!(spaces) >>
-        channels: opt!(argument_middle) >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::List { channels: channels.split_to_vec(","),
-                         server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"LIST") >>
+        channels: opt!(preceded!(call!(spaces, cfg), call!(argument_middle, cfg))) >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::List { channels: channels.split_to_vec(b',', &cfg.decoder),
+                         server })
     )
 );
 
-named!(
-    command_invite<&[u8], Command>,
+named_args!(
+    command_invite<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"INVITE") >>
-        spaces >>
-        nickname: argument_middle >>
-        spaces >>
-        channel: argument_maybe_last >>
-        (Command::Invite { nickname: nickname.to_string(), channel: channel.to_string() })
+        tag_no_case!(b"INVITE") >>
+        call!(spaces, cfg) >>
+        nickname: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        channel: call!(argument_maybe_last, cfg) >>
+        (Command::Invite { nickname, channel })
     )
 );
 
-named!(
-    command_kick<&[u8], Command>,
+/// Parses the channels and users of a KICK, failing at the channels unless there is either one
+/// channel or one for each user.
+fn kick_targets<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+) -> nom::IResult<&'a [u8], (Vec<Text<'a>>, Vec<Text<'a>>)> {
+    let (rest, channels) = argument_middle(input, cfg)?;
+    let (rest, _) = spaces(rest, cfg)?;
+    let (rest, users) = argument_maybe_last(rest, cfg)?;
+    let channels = channels.split_to_vec(b',', &cfg.decoder);
+    let users = users.split_to_vec(b',', &cfg.decoder);
+    if channels.len() != 1 && channels.len() != users.len() {
+        return Err(nom::Err::Failure(nom::Context::Code(
+            input,
+            ErrorKind::KickTargetMismatch.code(),
+        )));
+    }
+    Ok((rest, (channels, users)))
+}
+
+named_args!(
+    command_kick<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"KICK") >>
-        spaces >>
-        channel: argument_middle >>
-        spaces >>
-        user: argument_maybe_last >>
-        opt!(spaces) >>
-        comment: opt!(argument_maybe_last) >>
-        (Command::Kick { channel: channel.to_string(), user: user.to_string(), comment: comment.map(|c| c.to_string()) })
+        tag_no_case!(b"KICK") >>
+        call!(spaces, cfg) >>
+        targets: call!(kick_targets, cfg) >>
+        comment: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Kick { channels: targets.0, users: targets.1, comment })
     )
 );
 
-named!(
-    command_version<&[u8], Command>,
+named_args!(
+    command_version<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"VERSION") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Version { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"VERSION") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Version { server })
     )
 );
 
-named!(
-    command_stats<&[u8], Command>,
+named_args!(
+    command_stats<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"STATS") >>
-        opt!(spaces) >>
-        query: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Stats { query: query.map(|q| q.to_string()), server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"STATS") >>
+        query: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Stats { query, server })
     )
 );
 
-named!(
-    command_links_all_arguments<&[u8], Command>,
+named_args!(
+    command_links_all_arguments<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"LINKS") >>
-        spaces >>
-        remote_server: argument_middle >>
-        spaces >>
-        server_mask: argument_maybe_last >>
-        (Command::Links { remote_server: Some(remote_server.to_string()), server_mask: Some(server_mask.to_string()) })
+        tag_no_case!(b"LINKS") >>
+        call!(spaces, cfg) >>
+        remote_server: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        server_mask: call!(argument_maybe_last, cfg) >>
+        (Command::Links { remote_server: Some(remote_server), server_mask: Some(server_mask) })
     )
 );
 
-named!(
-    command_links_opt_server_mask<&[u8], Command>,
+named_args!(
+    command_links_opt_server_mask<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"LINKS") >>
-        opt!(spaces) >>
-        server_mask: opt!(argument_maybe_last) >>
-        (Command::Links { remote_server: None, server_mask: server_mask.map(|s| s.to_string()) })
+        tag_no_case!(b"LINKS") >>
+        server_mask: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Links { remote_server: None, server_mask })
     )
 );
 
-named!(
-    command_links<&[u8], Command>,
+named_args!(
+    command_links<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     alt!(
-        command_links_all_arguments |
-        command_links_opt_server_mask
+        call!(command_links_all_arguments, cfg) |
+        call!(command_links_opt_server_mask, cfg)
     )
 );
 
-named!(
-    command_time<&[u8], Command>,
+named_args!(
+    command_time<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"TIME") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Time { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"TIME") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Time { server })
     )
 );
 
-named!(
-    command_connect<&[u8], Command>,
+named_args!(
+    command_connect<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"CONNECT") >>
-        spaces >>
-        target_server: argument_maybe_last >>
-        opt!(spaces) >>
-        port: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        remote_server: opt!(argument_maybe_last) >>
-        (Command::Connect { target_server: target_server.to_string(),
-                            port: port.map(|p| p.to_string()),
-                            remote_server: remote_server.map(|r| r.to_string()) })
+        tag_no_case!(b"CONNECT") >>
+        call!(spaces, cfg) >>
+        target_server: call!(argument_maybe_last, cfg) >>
+        port: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        remote_server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Connect { target_server, port, remote_server })
     )
 );
 
-named!(
-    command_trace<&[u8], Command>,
+named_args!(
+    command_trace<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"TRACE") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Trace { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"TRACE") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Trace { server })
     )
 );
 
-named!(
-    command_admin<&[u8], Command>,
+named_args!(
+    command_admin<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"ADMIN") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Admin { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"ADMIN") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Admin { server })
     )
 );
 
-named!(
-    command_info<&[u8], Command>,
+named_args!(
+    command_info<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"INFO") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Info { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"INFO") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Info { server })
     )
 );
 
-named!(
-    command_privmsg<&[u8], Command>,
+named_args!(
+    command_privmsg<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"PRIVMSG") >>
-        spaces >>
-        receivers: argument_middle >>
-        spaces >>
-        message: argument_maybe_last >>
-        (Command::Privmsg { receivers: receivers.split_to_vec(","),
-                            message: message.to_string() })
+        tag_no_case!(b"PRIVMSG") >>
+        call!(spaces, cfg) >>
+        receivers: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        message: call!(argument_maybe_last, cfg) >>
+        (Command::Privmsg { receivers: receivers.split_to_vec(b',', &cfg.decoder),
+                            message })
     )
 );
 
-named!(
-    command_notice<&[u8], Command>,
+named_args!(
+    command_notice<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"NOTICE") >>
-        spaces >>
-        nickname: argument_middle >>
-        spaces >>
-        text: argument_maybe_last >>
-        (Command::Notice { nickname: nickname.to_string(), text: text.to_string() })
+        tag_no_case!(b"NOTICE") >>
+        call!(spaces, cfg) >>
+        receivers: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        text: call!(argument_maybe_last, cfg) >>
+        (Command::Notice { receivers: receivers.split_to_vec(b',', &cfg.decoder),
+                           text })
     )
 );
 
-named!(
-    command_who<&[u8], Command>,
+named_args!(
+    command_who<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"WHO") >>
-        spaces >>
+        tag_no_case!(b"WHO") >>
+        call!(spaces, cfg) >>
         name: opt!(
             do_parse!(
-                name: argument_maybe_last >>
-                spaces >>
+                name: call!(argument_maybe_last, cfg) >>
+                call!(spaces, cfg) >>
                 (name)
             )
         ) >>
-        o: opt!(argument_maybe_last) >>
-        (Command::Who { name: name.map(|n| n.to_string()), o: o.map(|o| o.to_string()) })
+        o: opt!(call!(argument_maybe_last, cfg)) >>
+        (Command::Who { name, o })
     )
 );
 
-named!(
-    command_whois<&[u8], Command>,
+named_args!(
+    command_whois<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"WHOIS") >>
-        spaces >>
+        tag_no_case!(b"WHOIS") >>
+        call!(spaces, cfg) >>
         server: opt!(
             do_parse!(
-                server: argument_middle >>
-                spaces >>
+                server: call!(argument_middle, cfg) >>
+                call!(spaces, cfg) >>
                 (server)
             )
         ) >>
-        nickmasks: argument_maybe_last >>
-        (Command::Whois { server: server.map(|s| s.to_string()),
-                          nickmasks: nickmasks.split_to_vec(",") })
+        nickmasks: call!(argument_maybe_last, cfg) >>
+        (Command::Whois { server,
+                          nickmasks: nickmasks.split_to_vec(b',', &cfg.decoder) })
     )
 );
 
-named!(
-    command_whowas<&[u8], Command>,
+named_args!(
+    command_whowas<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"WHOWAS") >>
-        spaces >>
-        nickname: argument_maybe_last >>
-        opt!(spaces) >>
-        count: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Whowas { nickname: nickname.to_string(), count: count.map(|c| c.to_string()), server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"WHOWAS") >>
+        call!(spaces, cfg) >>
+        nickname: call!(argument_maybe_last, cfg) >>
+        count: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Whowas { nickname, count, server })
     )
 );
 
-named!(
-    command_kill<&[u8], Command>,
+named_args!(
+    command_kill<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"KILL") >>
-        spaces >>
-        nickname: argument_middle >>
-        spaces >>
-        comment: argument_maybe_last >>
-        (Command::Kill { nickname: nickname.to_string(), comment: comment.to_string() })
+        tag_no_case!(b"KILL") >>
+        call!(spaces, cfg) >>
+        nickname: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        comment: call!(argument_maybe_last, cfg) >>
+        (Command::Kill { nickname, comment })
     )
 );
 
-named!(
-    command_ping<&[u8], Command>,
+named_args!(
+    command_ping<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"PING") >>
-        spaces >>
-        server1: argument_maybe_last >>
-        opt!(spaces) >>
-        server2: opt!(argument_maybe_last) >>
-        (Command::Ping { server1: server1.to_string(), server2: server2.map(|s2| s2.to_string()) })
+        tag_no_case!(b"PING") >>
+        call!(spaces, cfg) >>
+        server1: call!(argument_maybe_last, cfg) >>
+        server2: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Ping { server1, server2 })
     )
 );
 
-named!(
-    command_pong<&[u8], Command>,
+named_args!(
+    command_pong<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"PONG") >>
-        spaces >>
-        daemon1: argument_maybe_last >>
-        opt!(spaces) >>
-        daemon2: opt!(argument_maybe_last) >>
-        (Command::Pong { daemon1: daemon1.to_string(), daemon2: daemon2.map(|s2| s2.to_string()) })
+        tag_no_case!(b"PONG") >>
+        call!(spaces, cfg) >>
+        daemon1: call!(argument_maybe_last, cfg) >>
+        daemon2: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Pong { daemon1, daemon2 })
     )
 );
 
-named!(
-    command_error<&[u8], Command>,
+named_args!(
+    command_error<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"ERROR") >>
-        spaces >>
-        message: argument_maybe_last >>
-        (Command::Error { message: message.to_string() })
+        tag_no_case!(b"ERROR") >>
+        call!(spaces, cfg) >>
+        message: call!(argument_maybe_last, cfg) >>
+        (Command::Error { message })
     )
 );
 
-named!(
-    command_away<&[u8], Command>,
+named_args!(
+    command_away<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"AWAY") >>
+        tag_no_case!(b"AWAY") >>
         message: opt!(
             do_parse!(
-                spaces >>
-                message: argument_maybe_last >>
+                call!(spaces, cfg) >>
+                message: call!(argument_maybe_last, cfg) >>
                 (message)
             )
         ) >>
-        (Command::Away { message: message.map(|m| m.to_string()) })
+        (Command::Away { message })
     )
 );
 
-named!(
-    command_rehash<&[u8], Command>,
+named_args!(
+    command_rehash<'a>(_cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"REHASH") >>
+        tag_no_case!(b"REHASH") >>
         (Command::Rehash)
     )
 );
 
-named!(
-    command_restart<&[u8], Command>,
+named_args!(
+    command_restart<'a>(_cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"RESTART") >>
+        tag_no_case!(b"RESTART") >>
         (Command::Restart)
     )
 );
 
-named!(
-    command_summon<&[u8], Command>,
+named_args!(
+    command_summon<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"SUMMON") >>
-        spaces >>
-        user: argument_maybe_last >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Summon { user: user.to_string(), server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"SUMMON") >>
+        call!(spaces, cfg) >>
+        user: call!(argument_maybe_last, cfg) >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Summon { user, server })
     )
 );
 
-named!(
-    command_users<&[u8], Command>,
+named_args!(
+    command_users<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"USERS") >>
-        opt!(spaces) >>
-        server: opt!(argument_maybe_last) >>
-        (Command::Users { server: server.map(|s| s.to_string()) })
+        tag_no_case!(b"USERS") >>
+        server: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Users { server })
     )
 );
 
-named!(
-    command_wallops<&[u8], Command>,
+named_args!(
+    command_wallops<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"WALLOPS") >>
-        spaces >>
-        text: argument_maybe_last >>
-        (Command::Wallops { text: text.to_string() })
+        tag_no_case!(b"WALLOPS") >>
+        call!(spaces, cfg) >>
+        text: call!(argument_maybe_last, cfg) >>
+        (Command::Wallops { text })
     )
 );
 
-named!(
-    command_userhost<&[u8], Command>,
+named_args!(
+    command_userhost<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"USERHOST") >>
-        spaces >>
-        nick1: argument_maybe_last >>
-        opt!(spaces) >>
-        nick2: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        nick3: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        nick4: opt!(argument_maybe_last) >>
-        opt!(spaces) >>
-        nick5: opt!(argument_maybe_last) >>
+        tag_no_case!(b"USERHOST") >>
+        call!(spaces, cfg) >>
+        nick1: call!(argument_maybe_last, cfg) >>
+        nick2: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        nick3: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        nick4: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        nick5: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
         (Command::Userhost {
-            nicknames: vec![Some(nick1), nick2, nick3, nick4, nick5]
+            nicknames: [Some(nick1), nick2, nick3, nick4, nick5]
                 .iter()
                 .flat_map(|n| n.clone())
                 .collect() })
     )
 );
 
-named!(
-    command_ison<&[u8], Command>,
+named_args!(
+    command_ison<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
     do_parse!(
-        tag!(b"ISON") >>
-        spaces >>
+        tag_no_case!(b"ISON") >>
+        call!(spaces, cfg) >>
         nicknames: take_until_either!(b"\0\r\n") >>
-        (Command::Ison { nicknames: nicknames.split_to_vec(b" ") })
+        (Command::Ison { nicknames: nicknames.split_to_vec(b' ', &cfg.decoder) })
+    )
+);
+
+named_args!(
+    command_motd<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"MOTD") >>
+        target: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Motd { target })
+    )
+);
+
+named_args!(
+    command_lusers<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"LUSERS") >>
+        mask: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        target: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Lusers { mask, target })
+    )
+);
+
+named_args!(
+    command_service<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"SERVICE") >>
+        call!(spaces, cfg) >>
+        nickname: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        distribution: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        service_type: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        info: call!(argument_maybe_last, cfg) >>
+        (Command::Service { nickname, distribution, service_type, info })
+    )
+);
+
+named_args!(
+    command_squery<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"SQUERY") >>
+        call!(spaces, cfg) >>
+        servicename: call!(argument_middle, cfg) >>
+        call!(spaces, cfg) >>
+        text: call!(argument_maybe_last, cfg) >>
+        (Command::Squery { servicename, text })
+    )
+);
+
+named_args!(
+    command_servlist<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"SERVLIST") >>
+        mask: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        service_type: opt!(preceded!(call!(spaces, cfg), call!(argument_maybe_last, cfg))) >>
+        (Command::Servlist { mask, service_type })
+    )
+);
+
+named_args!(
+    command_die<'a>(_cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        tag_no_case!(b"DIE") >>
+        (Command::Die)
+    )
+);
+
+fn numeric_code(input: &[u8]) -> nom::IResult<&[u8], Reply> {
+    let (rest, digits) = try_parse!(input, take!(3));
+    let terminated = rest.first().is_none_or(|c| b" \0\r\n".contains(c));
+    if !terminated || !digits.iter().all(|d| d.is_ascii_digit()) {
+        return Err(nom::Err::Error(nom::Context::Code(
+            input,
+            nom::ErrorKind::Digit,
+        )));
+    }
+    let code = digits
+        .iter()
+        .fold(0, |code, d| code * 10 + u16::from(d - b'0'));
+    Ok((rest, Reply::from_code(code)))
+}
+
+named_args!(
+    command_numeric<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        code: numeric_code >>
+        params: call!(arguments, cfg) >>
+        (Command::Numeric { code, params: params.0, trailing: params.1 })
     )
 );
 
-named!(
-    command<&[u8], Command>,
-    switch!(
-        peek!(is_not!(b" \r")),
-        b"PASS" => call!(command_pass) |
-        b"NICK" => call!(command_nick) |
-        b"USER" => call!(command_user) |
-        b"SERVER" => call!(command_server) |
-        b"OPER" => call!(command_oper) |
-        b"QUIT" => call!(command_quit) |
-        b"SQUIT" => call!(command_squit) |
-        b"JOIN" => call!(command_join) |
-        b"PART" => call!(command_part) |
-        b"MODE" => call!(command_mode) |
-        b"TOPIC" => call!(command_topic) |
-        b"NAMES" => call!(command_names) |
-        b"LIST" => call!(command_list) |
-        b"INVITE" => call!(command_invite) |
-        b"KICK" => call!(command_kick) |
-        b"VERSION" => call!(command_version) |
-        b"STATS" => call!(command_stats) |
-        b"LINKS" => call!(command_links) |
-        b"TIME" => call!(command_time) |
-        b"CONNECT" => call!(command_connect) |
-        b"TRACE" => call!(command_trace) |
-        b"ADMIN" => call!(command_admin) |
-        b"INFO" => call!(command_info) |
-        b"PRIVMSG" => call!(command_privmsg) |
-        b"NOTICE" => call!(command_notice) |
-        b"WHO" => call!(command_who) |
-        b"WHOIS" => call!(command_whois) |
-        b"WHOWAS" => call!(command_whowas) |
-        b"KILL" => call!(command_kill) |
-        b"PING" => call!(command_ping) |
-        b"PONG" => call!(command_pong) |
-        b"ERROR" => call!(command_error) |
-        b"AWAY" => call!(command_away) |
-        b"REHASH" => call!(command_rehash) |
-        b"RESTART" => call!(command_restart) |
-        b"SUMMON" => call!(command_summon) |
-        b"USERS" => call!(command_users) |
-        b"WALLOPS" => call!(command_wallops) |
-        b"USERHOST" => call!(command_userhost) |
-        b"ISON" => call!(command_ison)
+/// Whether `verb` is a command name, which is either letters or a three-digit numeric.
+fn is_verb(verb: &[u8]) -> bool {
+    !verb.is_empty() && verb.iter().all(u8::is_ascii_alphabetic)
+        || verb.len() == 3 && verb.iter().all(u8::is_ascii_digit)
+}
+
+/// Uppercases an unrecognised verb, borrowing it when it already is.
+fn raw_verb(verb: &[u8]) -> Cow<'_, str> {
+    match String::from_utf8_lossy(verb) {
+        Cow::Borrowed(verb) if !verb.bytes().any(|b| b.is_ascii_lowercase()) => Cow::Borrowed(verb),
+        verb => Cow::Owned(verb.to_ascii_uppercase()),
+    }
+}
+
+named_args!(
+    command_raw<'a>(cfg: &ParserConfig)<&'a [u8], Command<'a>>,
+    do_parse!(
+        verb: verify!(is_not!(b" \0\r\n"), is_verb) >>
+        params: call!(arguments, cfg) >>
+        (Command::Raw { verb: raw_verb(verb), params: params.0, trailing: params.1 })
     )
 );
 
+fn command<'a>(input: &'a [u8], cfg: &ParserConfig) -> nom::IResult<&'a [u8], Command<'a>> {
+    let (_, verb) = try_parse!(input, peek!(is_not!(b" \0\r\n")));
+    // Verbs are case-insensitive. None of the known ones is longer than eight bytes, so longer
+    // verbs can go straight to the fallback without being uppercased.
+    let mut upper = [0; 8];
+    let verb = match upper.get_mut(..verb.len()) {
+        Some(upper) => {
+            upper.copy_from_slice(verb);
+            upper.make_ascii_uppercase();
+            &*upper
+        }
+        None => verb,
+    };
+    match verb {
+        b"PASS" => command_pass(input, cfg),
+        b"NICK" => command_nick(input, cfg),
+        b"USER" => command_user(input, cfg),
+        b"SERVER" => command_server(input, cfg),
+        b"OPER" => command_oper(input, cfg),
+        b"QUIT" => command_quit(input, cfg),
+        b"SQUIT" => command_squit(input, cfg),
+        b"JOIN" => command_join(input, cfg),
+        b"PART" => command_part(input, cfg),
+        b"MODE" => command_mode(input, cfg),
+        b"TOPIC" => command_topic(input, cfg),
+        b"NAMES" => command_names(input, cfg),
+        b"LIST" => command_list(input, cfg),
+        b"INVITE" => command_invite(input, cfg),
+        b"KICK" => command_kick(input, cfg),
+        b"VERSION" => command_version(input, cfg),
+        b"STATS" => command_stats(input, cfg),
+        b"LINKS" => command_links(input, cfg),
+        b"TIME" => command_time(input, cfg),
+        b"CONNECT" => command_connect(input, cfg),
+        b"TRACE" => command_trace(input, cfg),
+        b"ADMIN" => command_admin(input, cfg),
+        b"INFO" => command_info(input, cfg),
+        b"PRIVMSG" => command_privmsg(input, cfg),
+        b"NOTICE" => command_notice(input, cfg),
+        b"WHO" => command_who(input, cfg),
+        b"WHOIS" => command_whois(input, cfg),
+        b"WHOWAS" => command_whowas(input, cfg),
+        b"KILL" => command_kill(input, cfg),
+        b"PING" => command_ping(input, cfg),
+        b"PONG" => command_pong(input, cfg),
+        b"ERROR" => command_error(input, cfg),
+        b"AWAY" => command_away(input, cfg),
+        b"REHASH" => command_rehash(input, cfg),
+        b"RESTART" => command_restart(input, cfg),
+        b"SUMMON" => command_summon(input, cfg),
+        b"USERS" => command_users(input, cfg),
+        b"WALLOPS" => command_wallops(input, cfg),
+        b"USERHOST" => command_userhost(input, cfg),
+        b"ISON" => command_ison(input, cfg),
+        b"MOTD" => command_motd(input, cfg),
+        b"LUSERS" => command_lusers(input, cfg),
+        b"SERVICE" => command_service(input, cfg),
+        b"SQUERY" => command_squery(input, cfg),
+        b"SERVLIST" => command_servlist(input, cfg),
+        b"DIE" => command_die(input, cfg),
+        _ => alt!(input, call!(command_numeric, cfg) | call!(command_raw, cfg)),
+    }
+}
+
+/// A parsed message, borrowing from the line it was parsed from wherever it can.
 #[derive(PartialEq, Eq, Debug)]
-pub struct Message {
-    pub prefix: Option<Prefix>,
-    pub command: Command,
+pub struct Message<'a> {
+    pub tags: Tags<'a>,
+    pub prefix: Option<Prefix<'a>>,
+    pub command: Command<'a>,
+}
+
+impl<'a> Message<'a> {
+    /// Copies any borrowed data so that the message can outlive the input buffer.
+    pub fn into_owned(self) -> Message<'static> {
+        Message {
+            tags: self.tags.into_owned(),
+            prefix: self.prefix.map(Prefix::into_owned),
+            command: self.command.into_owned(),
+        }
+    }
 }
 
-named!(
-    pub parse_message<&[u8], Message>,
+named_args!(
+    message<'a>(cfg: &ParserConfig)<&'a [u8], Message<'a>>,
     do_parse!(
-        prefix: opt!(do_parse!(prefix: prefix >> spaces >> (prefix))) >>
-        command: command >>
-        tag!(b"\r\n") >>
-        (Message { prefix: prefix, command: command })
+        tags: opt!(do_parse!(peek!(tag!("@")) >> tags: return_error!(tags) >> return_error!(call!(spaces, cfg)) >> (tags))) >>
+        prefix: opt!(do_parse!(prefix: call!(prefix, cfg) >> return_error!(call!(spaces, cfg)) >> (prefix))) >>
+        command: call!(command, cfg) >>
+        call!(line_end, cfg) >>
+        (Message { tags: tags.unwrap_or_default(), prefix, command })
     )
 );
 
+/// Parses a single line using the default `ParserConfig`, which only accepts lines terminated by
+/// `\r\n` and decodes parameters as UTF-8.
+pub fn parse_message(input: &[u8]) -> Result<Message<'_>, ParseError> {
+    parse_message_with(input, &ParserConfig::default())
+}
+
+/// Parses a single line, including its terminator.
+pub fn parse_message_with<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+) -> Result<Message<'a>, ParseError> {
+    if let Err((offset, kind)) = cfg.limits.check(input) {
+        return Err(ParseError::at(input, offset, kind));
+    }
+    match message(input, cfg) {
+        Ok((rest, _)) if !rest.is_empty() => Err(ParseError::new(
+            input.len() - rest.len(),
+            Component::Terminator,
+            ErrorKind::TrailingData,
+        )),
+        Ok((_, message)) => Ok(message),
+        Err(err) => Err(ParseError::from_nom(input, err)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Asserts that each line is written back byte for byte after being parsed.
+    fn assert_round_trip(lines: &[&[u8]]) {
+        for line in lines {
+            let message = parse_message(line).unwrap();
+            assert_eq!(
+                message.to_bytes().unwrap(),
+                *line,
+                "{}",
+                String::from_utf8_lossy(line)
+            );
+        }
+    }
+
     #[test]
     fn pass() {
         assert_eq!(
-            command_pass(b"PASS password\r\n"),
+            command_pass(b"PASS password\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Pass {
@@ -859,7 +1906,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            command_pass(b"PASS :password\r\n"),
+            command_pass(b"PASS :password\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Pass {
@@ -869,7 +1916,7 @@ mod tests {
         );
 
         assert_eq!(
-            command_pass(b"PASS  password\r\n"),
+            command_pass(b"PASS  password\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Pass {
@@ -878,7 +1925,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            command_pass(b"PASS  :password\r\n"),
+            command_pass(b"PASS  :password\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Pass {
@@ -891,7 +1938,7 @@ mod tests {
     #[test]
     fn nick() {
         assert_eq!(
-            command_nick(b"NICK nickname\r\n"),
+            command_nick(b"NICK nickname\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Nick {
@@ -901,7 +1948,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            command_nick(b"NICK :nickname\r\n"),
+            command_nick(b"NICK :nickname\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Nick {
@@ -912,7 +1959,7 @@ mod tests {
         );
 
         assert_eq!(
-            command_nick(b"NICK  nickname  42\r\n"),
+            command_nick(b"NICK  nickname  42\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Nick {
@@ -922,7 +1969,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            command_nick(b"NICK nickname :42\r\n"),
+            command_nick(b"NICK nickname :42\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Nick {
@@ -936,27 +1983,30 @@ mod tests {
     #[test]
     fn user() {
         assert_eq!(
-            command_user(b"USER user host server real\r\n"),
+            command_user(b"USER user host server real\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::User {
-                    username: "user".to_string(),
-                    hostname: "host".to_string(),
-                    servername: "server".to_string(),
-                    realname: "real".to_string()
+                    username: "user".into(),
+                    hostname: "host".into(),
+                    servername: "server".into(),
+                    realname: "real".into()
                 }
             ))
         );
 
         assert_eq!(
-            command_user(b"USER user  host   server    :real name\r\n"),
+            command_user(
+                b"USER user  host   server    :real name\r\n",
+                &ParserConfig::default()
+            ),
             Ok((
                 &b"\r\n"[..],
                 Command::User {
-                    username: "user".to_string(),
-                    hostname: "host".to_string(),
-                    servername: "server".to_string(),
-                    realname: "real name".to_string()
+                    username: "user".into(),
+                    hostname: "host".into(),
+                    servername: "server".into(),
+                    realname: "real name".into()
                 }
             ))
         );
@@ -965,24 +2015,27 @@ mod tests {
     #[test]
     fn server() {
         assert_eq!(
-            command_server(b"SERVER foo 5 something\r\n"),
+            command_server(b"SERVER foo 5 something\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Server {
-                    servername: "foo".to_string(),
+                    servername: "foo".into(),
                     hopcount: 5,
-                    info: "something".to_string()
+                    info: "something".into()
                 }
             ))
         );
         assert_eq!(
-            command_server(b"SERVER foo    5  :this is some server!\r\n"),
+            command_server(
+                b"SERVER foo    5  :this is some server!\r\n",
+                &ParserConfig::default()
+            ),
             Ok((
                 &b"\r\n"[..],
                 Command::Server {
-                    servername: "foo".to_string(),
+                    servername: "foo".into(),
                     hopcount: 5,
-                    info: "this is some server!".to_string()
+                    info: "this is some server!".into()
                 }
             ))
         );
@@ -991,22 +2044,22 @@ mod tests {
     #[test]
     fn oper() {
         assert_eq!(
-            command_oper(b"OPER user pass\r\n"),
+            command_oper(b"OPER user pass\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Oper {
-                    user: "user".to_string(),
-                    password: "pass".to_string()
+                    user: "user".into(),
+                    password: "pass".into()
                 }
             ))
         );
         assert_eq!(
-            command_oper(b"OPER user  :pass\r\n"),
+            command_oper(b"OPER user  :pass\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Oper {
-                    user: "user".to_string(),
-                    password: "pass".to_string()
+                    user: "user".into(),
+                    password: "pass".into()
                 }
             ))
         );
@@ -1015,24 +2068,24 @@ mod tests {
     #[test]
     fn quit() {
         assert_eq!(
-            command_quit(b"QUIT\r\n"),
+            command_quit(b"QUIT\r\n", &ParserConfig::default()),
             Ok((&b"\r\n"[..], Command::Quit { message: None }))
         );
         assert_eq!(
-            command_quit(b"QUIT bye\r\n"),
+            command_quit(b"QUIT bye\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Quit {
-                    message: Some("bye".to_string())
+                    message: Some("bye".into())
                 }
             ))
         );
         assert_eq!(
-            command_quit(b"QUIT  :good bye\r\n"),
+            command_quit(b"QUIT  :good bye\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Quit {
-                    message: Some("good bye".to_string())
+                    message: Some("good bye".into())
                 }
             ))
         );
@@ -1041,22 +2094,22 @@ mod tests {
     #[test]
     fn squit() {
         assert_eq!(
-            command_squit(b"SQUIT server comment\r\n"),
+            command_squit(b"SQUIT server comment\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Squit {
-                    server: "server".to_string(),
-                    comment: "comment".to_string()
+                    server: "server".into(),
+                    comment: "comment".into()
                 }
             ))
         );
         assert_eq!(
-            command_squit(b"SQUIT server  :comment\r\n"),
+            command_squit(b"SQUIT server  :comment\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Squit {
-                    server: "server".to_string(),
-                    comment: "comment".to_string()
+                    server: "server".into(),
+                    comment: "comment".into()
                 }
             ))
         );
@@ -1065,32 +2118,46 @@ mod tests {
     #[test]
     fn join() {
         assert_eq!(
-            command_join(b"JOIN #foo\r\n"),
+            command_join(b"JOIN #foo\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Join {
-                    channels: vec!["#foo".to_string()],
+                    channels: vec!["#foo".into()],
                     keys: vec![],
                 }
             ))
         );
         assert_eq!(
-            command_join(b"JOIN #foo,#bar\r\n"),
+            command_join(b"JOIN #foo,#bar\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Join {
-                    channels: vec!["#foo".to_string(), "#bar".to_string()],
+                    channels: vec!["#foo".into(), "#bar".into()],
                     keys: vec![],
                 }
             ))
         );
         assert_eq!(
-            command_join(b"JOIN #foo,#bar  baz,quux\r\n"),
+            command_join(b"JOIN #foo,#bar  baz,quux\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Join {
+                    channels: vec!["#foo".into(), "#bar".into()],
+                    keys: vec!["baz".into(), "quux".into()],
+                }
+            ))
+        );
+        assert_eq!(
+            command_join(b"JOIN 0\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], Command::PartAll))
+        );
+        assert_eq!(
+            command_join(b"JOIN #0\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Join {
-                    channels: vec!["#foo".to_string(), "#bar".to_string()],
-                    keys: vec!["baz".to_string(), "quux".to_string()],
+                    channels: vec!["#0".into()],
+                    keys: vec![],
                 }
             ))
         );
@@ -1099,20 +2166,32 @@ mod tests {
     #[test]
     fn part() {
         assert_eq!(
-            command_part(b"PART #foo\r\n"),
+            command_part(b"PART #foo\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Part {
+                    channels: vec!["#foo".into()],
+                    message: None,
+                }
+            ))
+        );
+        assert_eq!(
+            command_part(b"PART  #foo,#bar\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Part {
-                    channels: vec!["#foo".to_string()],
+                    channels: vec!["#foo".into(), "#bar".into()],
+                    message: None,
                 }
             ))
         );
         assert_eq!(
-            command_part(b"PART  #foo,#bar\r\n"),
+            command_part(b"PART #foo :see you later\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Part {
-                    channels: vec!["#foo".to_string(), "#bar".to_string()],
+                    channels: vec!["#foo".into()],
+                    message: Some("see you later".into()),
                 }
             ))
         );
@@ -1122,151 +2201,852 @@ mod tests {
     fn mode() {
         let command = b"MODE #foo +b-q+l-i foo bar!*@* 42\r\n";
         let expected = Command::Mode {
-            target: "#foo".to_string(),
-            modechanges: Some(vec![
-                ChannelModeChange::Added(AddedChannelMode::Ban("foo".to_string())),
-                ChannelModeChange::Removed(RemovedChannelMode::Quiet("bar!*@*".to_string())),
+            target: "#foo".into(),
+            modechanges: ModeChanges::Channel(Some(vec![
+                ChannelModeChange::Added(AddedChannelMode::Ban("foo".into())),
+                ChannelModeChange::Removed(RemovedChannelMode::Quiet("bar!*@*".into())),
                 ChannelModeChange::Added(AddedChannelMode::Limit(42)),
                 ChannelModeChange::Removed(RemovedChannelMode::InviteOnly),
-            ]),
+            ])),
         };
 
         assert_eq!(
-            command_mode(&command[..]),
+            command_mode(&command[..], &ParserConfig::default()),
             Ok((&b"\r\n"[..], expected))
         );
-    }
 
-    #[test]
-    fn topic() {
         assert_eq!(
-            command_topic(b"TOPIC #channel\r\n"),
+            command_mode(b"MODE #foo\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
-                Command::Topic {
-                    channel: "#channel".to_string(),
-                    topic: None
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(None),
                 }
             ))
         );
+    }
+
+    #[test]
+    fn mode_round_trip() {
+        let cases: &[&str] = &[
+            "+o-v+b a b c",
+            "+b-q+l-i foo bar!*@* 42",
+            "-o+v-b+k a b *!*@c key",
+            "+ntk-l key",
+            "+b",
+        ];
+        for modes in cases {
+            let line = format!("{}\r\n", modes);
+            let (rest, changes) = channel_modes(line.as_bytes(), &ParserConfig::default()).unwrap();
+            assert_eq!(rest, b"\r\n");
+            let mut out = String::new();
+            changes.serialize(&mut out).unwrap();
+            assert_eq!(out, format!(" {}", modes));
+        }
+    }
+
+    #[test]
+    fn mode_split() {
+        fn lines(commands: &[Command]) -> Vec<String> {
+            commands
+                .iter()
+                .map(|command| {
+                    let mut out = String::new();
+                    command.serialize(&mut out).unwrap();
+                    out
+                })
+                .collect()
+        }
+
+        let mut table = ChannelModeTable::default();
+        assert_eq!(table.max_modes(), 3);
+        table.apply_isupport("MODES=4");
+        assert_eq!(table.max_modes(), 4);
+
+        let changes = vec![
+            ChannelModeChange::Added(AddedChannelMode::NoExternal),
+            ChannelModeChange::Added(AddedChannelMode::Op("a".into())),
+            ChannelModeChange::Added(AddedChannelMode::Op("b".into())),
+            ChannelModeChange::Added(AddedChannelMode::OpsTopic),
+            ChannelModeChange::Added(AddedChannelMode::Op("c".into())),
+            ChannelModeChange::Removed(RemovedChannelMode::Voice("d".into())),
+            ChannelModeChange::Added(AddedChannelMode::Voice("e".into())),
+        ];
+        let commands = Command::split_modes("#foo".into(), changes, 3, &Limits::default());
         assert_eq!(
-            command_topic(b"TOPIC #channel something\r\n"),
+            lines(&commands),
+            vec!["MODE #foo +nooto a b c", "MODE #foo -v+v d e"]
+        );
+
+        let changes = (0..40)
+            .map(|n| ChannelModeChange::Added(AddedChannelMode::Ban(format!("{:055}!*@*", n))))
+            .collect();
+        let commands = Command::split_modes("#foo".into(), changes, 100, &Limits::default());
+        assert_eq!(commands.len(), 5);
+        let mut masks = Vec::new();
+        for command in &commands {
+            let mut line = command.to_bytes().unwrap();
+            line.extend_from_slice(b"\r\n");
+            assert!(line.len() <= 512);
+            let (_, parsed) = command_mode(&line, &ParserConfig::default()).unwrap();
+            masks.extend(parsed.params().into_iter().skip(2).map(|p| p.to_string()));
+        }
+        assert_eq!(masks.len(), 40);
+        assert!(masks.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn channel_modes_state() {
+        let table = ChannelModeTable::default();
+        let mut modes = ChannelModes::new();
+        let line = b"MODE #foo +ntmk-t+lbbo secret 10 a!*@* b!*@* nick\r\n";
+        match command_mode(&line[..], &ParserConfig::default()) {
             Ok((
-                &b"\r\n"[..],
-                Command::Topic {
-                    channel: "#channel".to_string(),
-                    topic: Some("something".to_string())
-                }
-            ))
+                _,
+                Command::Mode {
+                    modechanges: ModeChanges::Channel(Some(changes)),
+                    ..
+                },
+            )) => modes.apply_all(&changes, &table),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(modes.is_moderated());
+        assert!(modes.is_no_external());
+        assert!(!modes.is_ops_topic());
+        assert_eq!(modes.key(), Some("secret"));
+        assert_eq!(modes.limit(), Some(10));
+        assert_eq!(
+            modes.bans().iter().collect::<Vec<_>>(),
+            vec!["a!*@*", "b!*@*"]
+        );
+        assert!(modes.is_op("nick"));
+
+        modes.apply_all(
+            &[
+                ChannelModeChange::Removed(RemovedChannelMode::Moderated),
+                ChannelModeChange::Removed(RemovedChannelMode::Key(Some("secret".into()))),
+                ChannelModeChange::Removed(RemovedChannelMode::Limit),
+                ChannelModeChange::Removed(RemovedChannelMode::Ban("a!*@*".into())),
+                ChannelModeChange::Removed(RemovedChannelMode::Op("nick".into())),
+                ChannelModeChange::Added(AddedChannelMode::Voice("nick".into())),
+                ChannelModeChange::Added(AddedChannelMode::Other('S', None)),
+                ChannelModeChange::Added(AddedChannelMode::Other('f', Some("5:10".into()))),
+                ChannelModeChange::ListQuery('b'),
+            ],
+            &table,
+        );
+        assert!(!modes.is_moderated());
+        assert_eq!(modes.key(), None);
+        assert_eq!(modes.limit(), None);
+        assert_eq!(modes.bans().iter().collect::<Vec<_>>(), vec!["b!*@*"]);
+        assert!(!modes.is_op("nick"));
+        assert!(modes.is_voiced("nick"));
+        assert!(modes.has_flag('S'));
+        assert_eq!(modes.setting('f'), Some("5:10"));
+
+        modes.apply(
+            &ChannelModeChange::Removed(RemovedChannelMode::Other('f', None)),
+            &table,
         );
+        assert_eq!(modes.setting('f'), None);
+
+        // Modes the table lists as list or membership modes keep every entry.
+        let table = ChannelModeTable::new("beIX,k,l,imnpst", "(Yqaohv)!~&@%+");
+        let cfg = ParserConfig {
+            channel_modes: table.clone(),
+            ..ParserConfig::default()
+        };
+        let (_, changes) = channel_modes(b"+YYXX-Y alice bob a!*@* b!*@* alice\r\n", &cfg).unwrap();
+        modes.apply_all(&changes, &table);
         assert_eq!(
-            command_topic(b"TOPIC #channel  :something else\r\n"),
-            Ok((
-                &b"\r\n"[..],
-                Command::Topic {
-                    channel: "#channel".to_string(),
-                    topic: Some("something else".to_string())
-                }
-            ))
+            modes.members('Y').unwrap().iter().collect::<Vec<_>>(),
+            vec!["bob"]
+        );
+        assert_eq!(
+            modes.list('X').unwrap().iter().collect::<Vec<_>>(),
+            vec!["a!*@*", "b!*@*"]
+        );
+        assert_eq!(modes.setting('Y'), None);
+
+        let mut target = modes.clone();
+        target.apply(
+            &ChannelModeChange::Removed(RemovedChannelMode::Other('Y', Some("bob".into()))),
+            &table,
+        );
+        assert_eq!(target.members('Y'), None);
+        assert_eq!(
+            modes.diff(&target, &table),
+            vec![ChannelModeChange::Removed(RemovedChannelMode::Other(
+                'Y',
+                Some("bob".into())
+            ))]
         );
     }
 
     #[test]
-    fn names() {
+    fn channel_modes_diff() {
+        let cfg = ParserConfig {
+            channel_modes: ChannelModeTable::new("beIq,k,lf,imnpstS", "(ov)@+"),
+            ..ParserConfig::default()
+        };
+        let state = |modes: &str| {
+            let line = format!("{}\r\n", modes);
+            let (_, changes) = channel_modes(line.as_bytes(), &cfg).unwrap();
+            let mut state = ChannelModes::new();
+            state.apply_all(&changes, &cfg.channel_modes);
+            state
+        };
+
+        let current = state("+ntklbbof old 10 a!*@* b!*@* alice 5:10");
+        let desired = state("+tmSkbbev new b!*@* c!*@* e!*@* bob");
+        let changes = current.diff(&desired, &cfg.channel_modes);
+        let mut out = String::new();
+        changes.serialize(&mut out).unwrap();
+        assert_eq!(out, " -nklfbo+Smkbev old a!*@* alice new c!*@* e!*@* bob");
+
+        let mut updated = current.clone();
+        updated.apply_all(&changes, &cfg.channel_modes);
+        assert_eq!(updated.diff(&desired, &cfg.channel_modes), vec![]);
+        assert_eq!(updated.key(), Some("new"));
+        assert_eq!(updated.limit(), None);
+        assert_eq!(updated.setting('f'), None);
+
+        assert_eq!(desired.diff(&desired, &cfg.channel_modes), vec![]);
         assert_eq!(
-            command_names(b"NAMES\r\n"),
-            Ok((&b"\r\n"[..], Command::Names { channels: vec![] }))
+            state("+l 10").diff(&state("+l 20"), &cfg.channel_modes),
+            vec![ChannelModeChange::Added(AddedChannelMode::Limit(20))]
         );
-        assert_eq!(
-            command_names(b"NAMES  :#foo,#bar\r\n"),
-            Ok((
-                &b"\r\n"[..],
-                Command::Names {
-                    channels: vec!["#foo".to_string(), "#bar".to_string()],
-                }
-            ))
+
+        // Settings in group B are removed with their value.
+        let unreal = ChannelModeTable::new("beI,fkL,lH,imnpst", "(ov)@+");
+        let mut current = ChannelModes::new();
+        current.apply_all(
+            &parse_channel_modes("+ntfL", &["[5j]:10", "#over"], &unreal).unwrap(),
+            &unreal,
         );
+        let changes = current.diff(&ChannelModes::new(), &unreal);
+        let (modes, args) = format_channel_modes(&changes);
+        assert_eq!(modes, "-ntLf");
+        assert_eq!(args, ["#over", "[5j]:10"]);
+        assert_eq!(parse_channel_modes(&modes, &args, &unreal), Ok(changes));
     }
 
     #[test]
-    fn list() {
+    fn parse_and_format_channel_modes() {
+        let table = ChannelModeTable::default();
+        let changes = parse_channel_modes("+ntkl-b+e", &["secret", "10", "a!*@*", "b!*@*"], &table);
         assert_eq!(
-            command_list(b"LIST\r\n"),
-            Ok((
-                &b"\r\n"[..],
-                Command::List {
-                    channels: vec![],
-                    server: None,
-                }
+            changes,
+            Ok(vec![
+                ChannelModeChange::Added(AddedChannelMode::NoExternal),
+                ChannelModeChange::Added(AddedChannelMode::OpsTopic),
+                ChannelModeChange::Added(AddedChannelMode::Key("secret".into())),
+                ChannelModeChange::Added(AddedChannelMode::Limit(10)),
+                ChannelModeChange::Removed(RemovedChannelMode::Ban("a!*@*".into())),
+                ChannelModeChange::Added(AddedChannelMode::BanException("b!*@*".into())),
+            ])
+        );
+        assert_eq!(
+            format_channel_modes(&changes.unwrap()),
+            (
+                "+ntkl-b+e".to_owned(),
+                vec![
+                    "secret".to_owned(),
+                    "10".to_owned(),
+                    "a!*@*".to_owned(),
+                    "b!*@*".to_owned()
+                ]
+            )
+        );
+
+        let none: &[&str] = &[];
+        assert_eq!(
+            parse_channel_modes("+b", none, &table),
+            Ok(vec![ChannelModeChange::ListQuery('b')])
+        );
+        assert_eq!(format_channel_modes(&[]), (String::new(), vec![]));
+
+        assert_eq!(
+            parse_channel_modes("nt", none, &table),
+            Err(ParseError::new(
+                0,
+                Component::ModeString,
+                ErrorKind::MissingModeSign
             ))
         );
         assert_eq!(
-            command_list(b"LIST #channel\r\n"),
-            Ok((
-                &b"\r\n"[..],
-                Command::List {
-                    channels: vec!["#channel".to_string()],
-                    server: None,
-                }
+            parse_channel_modes("+n t", none, &table),
+            Err(ParseError::new(
+                2,
+                Component::ModeString,
+                ErrorKind::InvalidModeString
             ))
         );
+        // MODE rejects the same mode strings at the same place.
+        for &(modes, offset) in &[("+é", 1), ("+", 0), ("+-n", 1), ("+n-", 2), ("+9", 1)] {
+            assert_eq!(
+                parse_channel_modes(modes, none, &table),
+                Err(ParseError::new(
+                    offset,
+                    Component::ModeString,
+                    ErrorKind::InvalidModeString
+                ))
+            );
+            let line = format!("MODE #c {}\r\n", modes);
+            assert_eq!(
+                parse_message(line.as_bytes()),
+                Err(ParseError::new(
+                    8 + offset,
+                    Component::ModeString,
+                    ErrorKind::InvalidModeString
+                ))
+            );
+        }
         assert_eq!(
-            command_list(b"LIST #channel  :irc.example.org\r\n"),
-            Ok((
-                &b"\r\n"[..],
-                Command::List {
-                    channels: vec!["#channel".to_string()],
-                    server: Some("irc.example.org".to_string()),
-                }
+            parse_channel_modes("+kl", &["key"], &table),
+            Err(ParseError::new(
+                7,
+                Component::Parameter(1),
+                ErrorKind::MissingParameter
+            ))
+        );
+        assert_eq!(
+            parse_channel_modes("+kl", &["key", "many"], &table),
+            Err(ParseError::new(
+                8,
+                Component::Parameter(1),
+                ErrorKind::InvalidLimit
+            ))
+        );
+        assert_eq!(
+            parse_channel_modes("+n", &["extra"], &table),
+            Err(ParseError::new(
+                3,
+                Component::Parameter(0),
+                ErrorKind::UnexpectedParameter
             ))
         );
     }
 
     #[test]
-    fn invite() {
+    fn mode_list_query() {
         assert_eq!(
-            command_invite(b"INVITE person #channel\r\n"),
+            command_mode(b"MODE #foo +b\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
-                Command::Invite {
-                    nickname: "person".to_string(),
-                    channel: "#channel".to_string()
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(Some(vec![ChannelModeChange::ListQuery(
+                        'b'
+                    )])),
                 }
             ))
         );
         assert_eq!(
-            command_invite(b"INVITE person  :#channel\r\n"),
+            command_mode(b"MODE #foo +eI\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
-                Command::Invite {
-                    nickname: "person".to_string(),
-                    channel: "#channel".to_string()
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(Some(vec![
+                        ChannelModeChange::ListQuery('e'),
+                        ChannelModeChange::ListQuery('I'),
+                    ])),
                 }
             ))
         );
-    }
-
+        assert_eq!(
+            command_mode(b"MODE #foo +b *!*@host\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(Some(vec![ChannelModeChange::Added(
+                        AddedChannelMode::Ban("*!*@host".into())
+                    )])),
+                }
+            ))
+        );
+        assert!(command_mode(b"MODE #foo -b\r\n", &ParserConfig::default()).is_err());
+
+        let line = b"MODE #foo +b\r\n";
+        assert_eq!(parse_message(line).unwrap().to_bytes().unwrap(), &line[..]);
+    }
+
+    #[test]
+    fn mode_table() {
+        let mut table = ChannelModeTable::default();
+        assert_eq!(table.kind('b'), Some(ChannelModeKind::List));
+        assert_eq!(table.kind('k'), Some(ChannelModeKind::Parameter));
+        assert_eq!(table.kind('l'), Some(ChannelModeKind::ParameterWhenSet));
+        assert_eq!(table.kind('t'), Some(ChannelModeKind::Flag));
+        assert_eq!(table.kind('o'), Some(ChannelModeKind::Prefix));
+        assert_eq!(table.kind('X'), None);
+
+        for token in &[
+            "NETWORK=Example",
+            "CHANMODES=beI,k,l,imnpstSR,extra",
+            "PREFIX=(qaohv)~&@%+",
+        ] {
+            table.apply_isupport(token);
+        }
+        assert_eq!(table.kind('q'), Some(ChannelModeKind::Prefix));
+        assert_eq!(table.kind('S'), Some(ChannelModeKind::Flag));
+        assert_eq!(table.kind('x'), None);
+        assert_eq!(
+            table.prefixes(),
+            &[('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')]
+        );
+
+        let cfg = ParserConfig {
+            channel_modes: table,
+            ..ParserConfig::default()
+        };
+        let line = b"MODE #foo +qhSX-k+o alice bob secret carol\r\n";
+        assert_eq!(
+            command_mode(&line[..], &cfg),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(Some(vec![
+                        ChannelModeChange::Added(AddedChannelMode::Owner("alice".into())),
+                        ChannelModeChange::Added(AddedChannelMode::HalfOp("bob".into())),
+                        ChannelModeChange::Added(AddedChannelMode::StripColors),
+                        ChannelModeChange::Added(AddedChannelMode::Other('X', None)),
+                        ChannelModeChange::Removed(RemovedChannelMode::Key(Some("secret".into()))),
+                        ChannelModeChange::Added(AddedChannelMode::Op("carol".into())),
+                    ])),
+                }
+            ))
+        );
+
+        let line = b"MODE #foo +qhSX-k alice bob secret\r\n";
+        let message = parse_message_with(line, &cfg).unwrap();
+        assert_eq!(message.to_bytes().unwrap(), &line[..]);
+
+        let cfg = ParserConfig {
+            channel_modes: ChannelModeTable::new("b,,,", ""),
+            ..ParserConfig::default()
+        };
+        assert_eq!(
+            command_mode(b"MODE #foo +bo mask\r\n", &cfg),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "#foo".into(),
+                    modechanges: ModeChanges::Channel(Some(vec![
+                        ChannelModeChange::Added(AddedChannelMode::Ban("mask".into())),
+                        ChannelModeChange::Added(AddedChannelMode::Other('o', None)),
+                    ])),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn network_channel_modes() {
+        let table = ChannelModeTable::new("beIq,k,ljfL,aimnprstcCRSzQ", "(ov)@+");
+        let changes = parse_channel_modes(
+            "+arcCRSzQjfLq-jfLqa",
+            &["3:10", "5:10", "#overflow", "a!*@*", "a!*@*"],
+            &table,
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                ChannelModeChange::Added(AddedChannelMode::Anonymous),
+                ChannelModeChange::Added(AddedChannelMode::Reop),
+                ChannelModeChange::Added(AddedChannelMode::NoColors),
+                ChannelModeChange::Added(AddedChannelMode::NoCtcp),
+                ChannelModeChange::Added(AddedChannelMode::RegisteredOnly),
+                ChannelModeChange::Added(AddedChannelMode::StripColors),
+                ChannelModeChange::Added(AddedChannelMode::SecureOnly),
+                ChannelModeChange::Added(AddedChannelMode::NoKicks),
+                ChannelModeChange::Added(AddedChannelMode::JoinThrottle("3:10".into())),
+                ChannelModeChange::Added(AddedChannelMode::Flood("5:10".into())),
+                ChannelModeChange::Added(AddedChannelMode::Link("#overflow".into())),
+                ChannelModeChange::Added(AddedChannelMode::Quiet("a!*@*".into())),
+                ChannelModeChange::Removed(RemovedChannelMode::JoinThrottle(None)),
+                ChannelModeChange::Removed(RemovedChannelMode::Flood(None)),
+                ChannelModeChange::Removed(RemovedChannelMode::Link(None)),
+                ChannelModeChange::Removed(RemovedChannelMode::Quiet("a!*@*".into())),
+                ChannelModeChange::Removed(RemovedChannelMode::Anonymous),
+            ]
+        );
+        let (modes, args) = format_channel_modes(&changes);
+        assert_eq!(modes, "+arcCRSzQjfLq-jfLqa");
+        assert_eq!(args, ["3:10", "5:10", "#overflow", "a!*@*", "a!*@*"]);
+
+        let table = ChannelModeTable::new("beI,k,l,imnpst", "(qaohv)~&@%+");
+        assert_eq!(
+            parse_channel_modes("+qah-o", &["alice", "bob", "carol", "dave"], &table),
+            Ok(vec![
+                ChannelModeChange::Added(AddedChannelMode::Owner("alice".into())),
+                ChannelModeChange::Added(AddedChannelMode::Admin("bob".into())),
+                ChannelModeChange::Added(AddedChannelMode::HalfOp("carol".into())),
+                ChannelModeChange::Removed(RemovedChannelMode::Op("dave".into())),
+            ])
+        );
+
+        let unreal = ChannelModeTable::new("beI,fkL,lj,imnpst", "(ov)@+");
+        let removed = parse_channel_modes("-Lf", &["#overflow", "[5j]:10"], &unreal).unwrap();
+        assert_eq!(
+            removed,
+            vec![
+                ChannelModeChange::Removed(RemovedChannelMode::Link(Some("#overflow".into()))),
+                ChannelModeChange::Removed(RemovedChannelMode::Flood(Some("[5j]:10".into()))),
+            ]
+        );
+        assert_eq!(
+            format_channel_modes(&removed),
+            (
+                "-Lf".to_owned(),
+                vec!["#overflow".to_owned(), "[5j]:10".to_owned()]
+            )
+        );
+
+        let mut state = ChannelModes::new();
+        state.apply_all(&changes, &table);
+        assert!(state.has_flag('z'));
+        assert_eq!(state.setting('j'), None);
+        state.apply(
+            &ChannelModeChange::Added(AddedChannelMode::Flood("5:10".into())),
+            &table,
+        );
+        assert_eq!(state.setting('f'), Some("5:10"));
+
+        let line = b"MODE #foo +W-Y+Z arg\r\n";
+        let cfg = ParserConfig {
+            channel_modes: ChannelModeTable::new(",,Z,", ""),
+            ..ParserConfig::default()
+        };
+        let message = parse_message_with(line, &cfg).unwrap();
+        match message.command {
+            Command::Mode {
+                modechanges: ModeChanges::Channel(Some(ref changes)),
+                ..
+            } => assert_eq!(
+                changes,
+                &[
+                    ChannelModeChange::Added(AddedChannelMode::Other('W', None)),
+                    ChannelModeChange::Removed(RemovedChannelMode::Other('Y', None)),
+                    ChannelModeChange::Added(AddedChannelMode::Other('Z', Some("arg".into()))),
+                ]
+            ),
+            ref command => panic!("unexpected {:?}", command),
+        }
+        assert_eq!(message.to_bytes().unwrap(), &line[..]);
+    }
+
+    #[test]
+    fn mode_trailing_parameter() {
+        let modes = |line: &'static [u8]| parse_message(line).map(|message| message.command);
+        assert_eq!(
+            modes(b":nick!user@host MODE nick :+iw\r\n"),
+            Ok(Command::Mode {
+                target: "nick".into(),
+                modechanges: ModeChanges::User(Some(vec![
+                    UserModeChange::Added(UserMode::Invisible),
+                    UserModeChange::Added(UserMode::Wallops),
+                ])),
+            })
+        );
+        assert_eq!(
+            modes(b":irc.example.org MODE nick +iw :\r\n"),
+            Ok(Command::Mode {
+                target: "nick".into(),
+                modechanges: ModeChanges::User(Some(vec![
+                    UserModeChange::Added(UserMode::Invisible),
+                    UserModeChange::Added(UserMode::Wallops),
+                ])),
+            })
+        );
+        assert_eq!(
+            modes(b":irc.example.org MODE #foo :+nt\r\n"),
+            Ok(Command::Mode {
+                target: "#foo".into(),
+                modechanges: ModeChanges::Channel(Some(vec![
+                    ChannelModeChange::Added(AddedChannelMode::NoExternal),
+                    ChannelModeChange::Added(AddedChannelMode::OpsTopic),
+                ])),
+            })
+        );
+        assert_eq!(
+            modes(b":op!user@host MODE #foo +o :alice\r\n"),
+            Ok(Command::Mode {
+                target: "#foo".into(),
+                modechanges: ModeChanges::Channel(Some(vec![ChannelModeChange::Added(
+                    AddedChannelMode::Op("alice".into())
+                )])),
+            })
+        );
+        assert_eq!(
+            modes(b"MODE #foo +b :\r\n"),
+            Ok(Command::Mode {
+                target: "#foo".into(),
+                modechanges: ModeChanges::Channel(Some(vec![ChannelModeChange::ListQuery('b')])),
+            })
+        );
+        assert_eq!(
+            modes(b"MODE #foo +k :\r\n"),
+            Err(ParseError::new(
+                13,
+                Component::ModeString,
+                ErrorKind::InvalidKey
+            ))
+        );
+    }
+
+    #[test]
+    fn user_mode() {
+        assert_eq!(
+            command_mode(b"MODE nick +iwx-o\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(Some(vec![
+                        UserModeChange::Added(UserMode::Invisible),
+                        UserModeChange::Added(UserMode::Wallops),
+                        UserModeChange::Added(UserMode::Other('x')),
+                        UserModeChange::Removed(UserMode::Oper),
+                    ])),
+                }
+            ))
+        );
+        assert_eq!(
+            command_mode(b"MODE nick -r+Os +cCk\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(Some(vec![
+                        UserModeChange::Removed(UserMode::Registered),
+                        UserModeChange::Added(UserMode::LocalOper),
+                        UserModeChange::Added(UserMode::ServerNotices(Some("+cCk".into()))),
+                    ])),
+                }
+            ))
+        );
+        assert_eq!(
+            command_mode(b"MODE nick +s\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(Some(vec![UserModeChange::Added(
+                        UserMode::ServerNotices(None)
+                    )])),
+                }
+            ))
+        );
+        assert_eq!(
+            command_mode(b"MODE nick\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(None),
+                }
+            ))
+        );
+
+        assert_eq!(
+            command_mode(b"MODE nick +si x\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(Some(vec![
+                        UserModeChange::Added(UserMode::ServerNotices(Some("x".into()))),
+                        UserModeChange::Added(UserMode::Invisible),
+                    ])),
+                }
+            ))
+        );
+        // With more than one parameter left, only one that starts with a sign is a snomask.
+        assert_eq!(
+            command_mode(b"MODE nick +si x y\r\n", &ParserConfig::default()),
+            Ok((
+                &b" x y\r\n"[..],
+                Command::Mode {
+                    target: "nick".into(),
+                    modechanges: ModeChanges::User(Some(vec![
+                        UserModeChange::Added(UserMode::ServerNotices(None)),
+                        UserModeChange::Added(UserMode::Invisible),
+                    ])),
+                }
+            ))
+        );
+        assert_eq!(
+            parse_message(b"MODE nick +si x y\r\n"),
+            Err(ParseError::new(
+                13,
+                Component::ModeString,
+                ErrorKind::UnexpectedParameter
+            ))
+        );
+
+        assert_round_trip(&[
+            b"MODE nick +iwx-o\r\n",
+            b"MODE nick -r+Os +cCk\r\n",
+            b"MODE nick\r\n",
+        ]);
+    }
+
+    #[test]
+    fn topic() {
+        assert_eq!(
+            command_topic(b"TOPIC #channel\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Topic {
+                    channel: "#channel".into(),
+                    topic: None
+                }
+            ))
+        );
+        assert_eq!(
+            command_topic(b"TOPIC #channel something\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Topic {
+                    channel: "#channel".into(),
+                    topic: Some("something".into())
+                }
+            ))
+        );
+        assert_eq!(
+            command_topic(
+                b"TOPIC #channel  :something else\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::Topic {
+                    channel: "#channel".into(),
+                    topic: Some("something else".into())
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            command_names(b"NAMES\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], Command::Names { channels: vec![] }))
+        );
+        assert_eq!(
+            command_names(b"NAMES  :#foo,#bar\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Names {
+                    channels: vec!["#foo".into(), "#bar".into()],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn list() {
+        assert_eq!(
+            command_list(b"LIST\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::List {
+                    channels: vec![],
+                    server: None,
+                }
+            ))
+        );
+        assert_eq!(
+            command_list(b"LIST #channel\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::List {
+                    channels: vec!["#channel".into()],
+                    server: None,
+                }
+            ))
+        );
+        assert_eq!(
+            command_list(
+                b"LIST #channel  :irc.example.org\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::List {
+                    channels: vec!["#channel".into()],
+                    server: Some("irc.example.org".into()),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn invite() {
+        assert_eq!(
+            command_invite(b"INVITE person #channel\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Invite {
+                    nickname: "person".into(),
+                    channel: "#channel".into()
+                }
+            ))
+        );
+        assert_eq!(
+            command_invite(b"INVITE person  :#channel\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Invite {
+                    nickname: "person".into(),
+                    channel: "#channel".into()
+                }
+            ))
+        );
+    }
+
     #[test]
     fn kick() {
         assert_eq!(
-            command_kick(b"KICK #channel person\r\n"),
+            command_kick(b"KICK #channel person\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Kick {
-                    channel: "#channel".to_string(),
-                    user: "person".to_string(),
+                    channels: vec!["#channel".into()],
+                    users: vec!["person".into()],
                     comment: None
                 }
             ))
         );
         assert_eq!(
-            command_kick(b"KICK #channel person  :some message\r\n"),
+            command_kick(
+                b"KICK #channel person  :some message\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::Kick {
+                    channels: vec!["#channel".into()],
+                    users: vec!["person".into()],
+                    comment: Some("some message".into())
+                }
+            ))
+        );
+        assert_eq!(
+            command_kick(b"KICK #a,#b alice,bob :out\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Kick {
-                    channel: "#channel".to_string(),
-                    user: "person".to_string(),
-                    comment: Some("some message".to_string())
+                    channels: vec!["#a".into(), "#b".into()],
+                    users: vec!["alice".into(), "bob".into()],
+                    comment: Some("out".into())
                 }
             ))
         );
@@ -1275,24 +3055,24 @@ mod tests {
     #[test]
     fn version() {
         assert_eq!(
-            command_version(b"VERSION\r\n"),
+            command_version(b"VERSION\r\n", &ParserConfig::default()),
             Ok((&b"\r\n"[..], Command::Version { server: None }))
         );
         assert_eq!(
-            command_version(b"VERSION irc.example.org\r\n"),
+            command_version(b"VERSION irc.example.org\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Version {
-                    server: Some("irc.example.org".to_string())
+                    server: Some("irc.example.org".into())
                 }
             ))
         );
         assert_eq!(
-            command_version(b"VERSION  :irc.example.org\r\n"),
+            command_version(b"VERSION  :irc.example.org\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
                 Command::Version {
-                    server: Some("irc.example.org".to_string())
+                    server: Some("irc.example.org".into())
                 }
             ))
         );
@@ -1330,9 +3110,29 @@ mod tests {
     #[test]
     fn privmsg() {}
 
-    // Notice { nickname: String, text: String },
     #[test]
-    fn notice() {}
+    fn notice() {
+        assert_eq!(
+            command_notice(
+                b"NOTICE alice,#foo :hello there\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::Notice {
+                    receivers: vec!["alice".into(), "#foo".into()],
+                    text: "hello there".into()
+                }
+            ))
+        );
+        assert_eq!(
+            parse_message(b"NOTICE alice,#foo :hello there\r\n")
+                .unwrap()
+                .to_bytes()
+                .unwrap(),
+            &b"NOTICE alice,#foo :hello there\r\n"[..]
+        );
+    }
 
     // Who { name: Option<String>, o: Option<String> },
     #[test]
@@ -1395,156 +3195,1255 @@ mod tests {
     fn ison() {}
 
     #[test]
-    fn test_prefix() {
+    fn motd() {
         assert_eq!(
-            prefix(b":foo.bar PRIVMSG #baz :quux"),
-            Ok((&b" PRIVMSG #baz :quux"[..], Prefix("foo.bar".to_string())))
+            command_motd(b"MOTD\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], Command::Motd { target: None }))
         );
-    }
-
-    #[test]
-    fn test_argument_middle() {
         assert_eq!(
-            argument_middle(b"foo :baz"),
-            Ok((&b" :baz"[..], "foo".to_owned()))
+            command_motd(b"MOTD irc.example.org\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Motd {
+                    target: Some("irc.example.org".into())
+                }
+            ))
         );
     }
 
     #[test]
-    fn test_argument_middle_initial_colon_not_allowed() {
+    fn lusers() {
         assert_eq!(
-            argument_middle(b":foo baz"),
-            Err(nom::Err::Error(nom::Context::Code(
-                &b":foo baz"[..],
-                nom::ErrorKind::Verify
-            )))
+            command_lusers(b"LUSERS\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Lusers {
+                    mask: None,
+                    target: None
+                }
+            ))
+        );
+        assert_eq!(
+            command_lusers(b"LUSERS *.fi irc.example.org\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Lusers {
+                    mask: Some("*.fi".into()),
+                    target: Some("irc.example.org".into())
+                }
+            ))
         );
     }
 
     #[test]
-    fn test_argument_trailing() {
+    fn service() {
         assert_eq!(
-            argument_trailing(b":foo bar baz\r\n"),
-            Ok((&b"\r\n"[..], "foo bar baz".to_owned()))
+            command_service(
+                b"SERVICE dict * *.fr 0 0 :French Dictionary\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::Service {
+                    nickname: "dict".into(),
+                    distribution: "*.fr".into(),
+                    service_type: "0".into(),
+                    info: "French Dictionary".into()
+                }
+            ))
         );
+        assert!(command_service(b"SERVICE dict * *.fr\r\n", &ParserConfig::default()).is_err());
     }
 
     #[test]
-    fn test_argument_trailing_empty() {
+    fn squery() {
         assert_eq!(
-            argument_trailing(b":\r\n"),
-            Ok((&b"\r\n"[..], "".to_owned()))
+            command_squery(
+                b"SQUERY irchelp :HELP privmsg\r\n",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b"\r\n"[..],
+                Command::Squery {
+                    servicename: "irchelp".into(),
+                    text: "HELP privmsg".into()
+                }
+            ))
         );
     }
 
     #[test]
-    fn test_command_privmsg() {
+    fn servlist() {
         assert_eq!(
-            command_privmsg(b"PRIVMSG #foo,#bar baz\r\n"),
+            command_servlist(b"SERVLIST\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
-                Command::Privmsg {
-                    receivers: vec!["#foo".to_string(), "#bar".to_string()],
-                    message: "baz".to_string(),
+                Command::Servlist {
+                    mask: None,
+                    service_type: None
                 }
             ))
         );
-
         assert_eq!(
-            command_privmsg(b"PRIVMSG #foo,#bar :baz quux\r\n"),
+            command_servlist(b"SERVLIST *serv 0\r\n", &ParserConfig::default()),
             Ok((
                 &b"\r\n"[..],
-                Command::Privmsg {
-                    receivers: vec!["#foo".to_string(), "#bar".to_string()],
-                    message: "baz quux".to_string(),
+                Command::Servlist {
+                    mask: Some("*serv".into()),
+                    service_type: Some("0".into())
                 }
             ))
         );
     }
 
     #[test]
-    fn test_message() {
+    fn die() {
         assert_eq!(
-            parse_message(b":irc.example.org PRIVMSG #foo :bar baz\r\n"),
-            Ok((
-                &b""[..],
-                Message {
-                    prefix: Some(Prefix("irc.example.org".to_string())),
-                    command: Command::Privmsg {
-                        receivers: vec!["#foo".into()],
-                        message: "bar baz".into(),
-                    },
-                }
-            ))
+            command_die(b"DIE\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], Command::Die))
         );
     }
 
     #[test]
-    fn test_whois() {
-        assert_eq!(
-            parse_message(b"WHO kyrias\r\n"),
+    fn test_part_round_trip() {
+        assert_round_trip(&[
+            b"PART #foo\r\n",
+            b"PART #foo,#bar :see you later\r\n",
+            b"JOIN 0\r\n",
+        ]);
+    }
+
+    #[test]
+    fn test_service_commands_round_trip() {
+        let lines: &[&[u8]] = &[
+            b"MOTD\r\n",
+            b"MOTD irc.example.org\r\n",
+            b"LUSERS *.fi irc.example.org\r\n",
+            b"SERVICE dict * *.fr 0 0 :French Dictionary\r\n",
+            b"SQUERY irchelp :HELP privmsg\r\n",
+            b"SERVLIST *serv 0\r\n",
+            b"DIE\r\n",
+        ];
+        assert_round_trip(lines);
+        for line in lines {
+            let message = parse_message(line).unwrap();
+            let command = &message.command;
+            let params = command.params();
+            assert_eq!(
+                Command::from_parts(&command.verb(), &params),
+                Ok(parse_message(line).unwrap().command.into_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(
+            prefix(b":foo.bar PRIVMSG #baz :quux", &ParserConfig::default()),
+            Ok((
+                &b" PRIVMSG #baz :quux"[..],
+                Prefix::Server("foo.bar".into())
+            ))
+        );
+        assert_eq!(
+            prefix(
+                b":nick!user@host.example PRIVMSG #baz :quux",
+                &ParserConfig::default()
+            ),
             Ok((
-                &b""[..],
-                Message {
-                    prefix: None,
-                    command: Command::Who {
-                        name: None,
-                        o: Some("kyrias".into())
-                    }
+                &b" PRIVMSG #baz :quux"[..],
+                Prefix::User {
+                    nick: "nick".into(),
+                    user: Some("user".into()),
+                    host: Some("host.example".into()),
                 }
             ))
         );
         assert_eq!(
-            parse_message(b"WHO kyrias foo\r\n"),
+            prefix(
+                b":nick@host.example PRIVMSG #baz :quux",
+                &ParserConfig::default()
+            ),
+            Ok((
+                &b" PRIVMSG #baz :quux"[..],
+                Prefix::new_user("nick", None, Some("host.example".into()))
+            ))
+        );
+        assert_eq!(
+            prefix(b":nick PRIVMSG #baz :quux", &ParserConfig::default()),
+            Ok((
+                &b" PRIVMSG #baz :quux"[..],
+                Prefix::new_user("nick", None, None)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_prefix_accessors() {
+        let user = Prefix::from("nick!~user@host.example");
+        assert_eq!(user.nick(), Some("nick"));
+        assert_eq!(user.user(), Some("~user"));
+        assert_eq!(user.host(), Some("host.example"));
+        assert_eq!(user.server_name(), None);
+
+        let server = Prefix::from("irc.example.org");
+        assert_eq!(server.nick(), None);
+        assert_eq!(server.server_name(), Some("irc.example.org"));
+    }
+
+    #[test]
+    fn test_prefix_round_trip() {
+        for p in &[
+            "irc.example.org",
+            "nick",
+            "nick@host",
+            "nick!user@host",
+            "nick!user",
+        ] {
+            let mut out = String::new();
+            Prefix::from(*p).serialize(&mut out).unwrap();
+            assert_eq!(out, format!(":{}", p));
+        }
+    }
+
+    #[test]
+    fn test_argument_middle() {
+        assert_eq!(
+            argument_middle(b"foo :baz", &ParserConfig::default()),
+            Ok((&b" :baz"[..], "foo".into()))
+        );
+    }
+
+    #[test]
+    fn test_argument_middle_initial_colon_not_allowed() {
+        assert_eq!(
+            argument_middle(b":foo baz", &ParserConfig::default()),
+            Err(nom::Err::Error(nom::Context::Code(
+                &b":foo baz"[..],
+                nom::ErrorKind::Verify
+            )))
+        );
+    }
+
+    #[test]
+    fn test_argument_trailing() {
+        assert_eq!(
+            argument_trailing(b":foo bar baz\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], "foo bar baz".into()))
+        );
+    }
+
+    #[test]
+    fn test_argument_trailing_empty() {
+        assert_eq!(
+            argument_trailing(b":\r\n", &ParserConfig::default()),
+            Ok((&b"\r\n"[..], "".into()))
+        );
+    }
+
+    #[test]
+    fn test_command_privmsg() {
+        assert_eq!(
+            command_privmsg(b"PRIVMSG #foo,#bar baz\r\n", &ParserConfig::default()),
             Ok((
-                &b""[..],
-                Message {
-                    prefix: None,
-                    command: Command::Who {
-                        name: Some("kyrias".into()),
-                        o: Some("foo".into())
-                    }
+                &b"\r\n"[..],
+                Command::Privmsg {
+                    receivers: vec!["#foo".into(), "#bar".into()],
+                    message: "baz".into(),
                 }
             ))
         );
+
         assert_eq!(
-            parse_message(b"WHOIS kyrias\r\n"),
+            command_privmsg(b"PRIVMSG #foo,#bar :baz quux\r\n", &ParserConfig::default()),
             Ok((
-                &b""[..],
-                Message {
-                    prefix: None,
-                    command: Command::Whois {
-                        server: None,
-                        nickmasks: vec!["kyrias".into()],
-                    },
+                &b"\r\n"[..],
+                Command::Privmsg {
+                    receivers: vec!["#foo".into(), "#bar".into()],
+                    message: "baz quux".into(),
                 }
             ))
         );
+    }
+
+    #[test]
+    fn test_message() {
         assert_eq!(
-            parse_message(b"WHOIS kyrias,demize\r\n"),
+            parse_message(b":irc.example.org PRIVMSG #foo :bar baz\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: Some(Prefix::Server("irc.example.org".into())),
+                command: Command::Privmsg {
+                    receivers: vec!["#foo".into()],
+                    message: "bar baz".into(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let cases: &[(&[u8], usize, Component, ErrorKind)] = &[
+            (
+                b"PRIVMSG #foo :bar",
+                17,
+                Component::Terminator,
+                ErrorKind::Incomplete,
+            ),
+            (b":nick\r\n", 5, Component::Verb, ErrorKind::InvalidVerb),
+            (b"@a=b :n\r\n", 7, Component::Verb, ErrorKind::InvalidVerb),
+            (
+                b":nick!user@host",
+                15,
+                Component::Terminator,
+                ErrorKind::Incomplete,
+            ),
+            (
+                b"USER user host\r\n",
+                14,
+                Component::Parameter(2),
+                ErrorKind::MissingParameter,
+            ),
+            (
+                b":irc.example.org SERVER foo bar :info\r\n",
+                28,
+                Component::Parameter(1),
+                ErrorKind::InvalidNumber,
+            ),
+            (
+                b"KICK #a,#b u1,u2,u3 :x\r\n",
+                5,
+                Component::Parameter(0),
+                ErrorKind::KickTargetMismatch,
+            ),
+            (
+                b"INVITE nick #foo extra\r\n",
+                16,
+                Component::Parameter(2),
+                ErrorKind::UnexpectedParameter,
+            ),
+            (
+                b"MODE #foo X\r\n",
+                10,
+                Component::ModeString,
+                ErrorKind::MissingModeSign,
+            ),
+            (
+                b"MODE #foo +9\r\n",
+                11,
+                Component::ModeString,
+                ErrorKind::InvalidModeString,
+            ),
+            (
+                b"MODE #foo +n\xc3\xa9\r\n",
+                12,
+                Component::ModeString,
+                ErrorKind::InvalidModeString,
+            ),
+            (
+                b"MODE #foo +\r\n",
+                10,
+                Component::ModeString,
+                ErrorKind::InvalidModeString,
+            ),
+            (
+                b"MODE #foo :+n-\r\n",
+                13,
+                Component::ModeString,
+                ErrorKind::InvalidModeString,
+            ),
+            (
+                b"MODE nick +-i\r\n",
+                11,
+                Component::ModeString,
+                ErrorKind::InvalidModeString,
+            ),
+            (
+                b"MODE #foo +l abc\r\n",
+                13,
+                Component::ModeString,
+                ErrorKind::InvalidLimit,
+            ),
+            (
+                b"MODE #foo +tl 99999999999999999999999\r\n",
+                14,
+                Component::ModeString,
+                ErrorKind::InvalidLimit,
+            ),
+            (
+                b"MODE #foo +k :a b\r\n",
+                13,
+                Component::ModeString,
+                ErrorKind::InvalidKey,
+            ),
+            (
+                b"MODE #foo +ob nick a!b\r\n",
+                19,
+                Component::ModeString,
+                ErrorKind::InvalidMask,
+            ),
+            (
+                b"MODE #foo +v a,b\r\n",
+                13,
+                Component::ModeString,
+                ErrorKind::InvalidModeArgument,
+            ),
+            (
+                b"PING irc.example.org\n",
+                20,
+                Component::Terminator,
+                ErrorKind::InvalidTerminator,
+            ),
+            (
+                b"@ PING irc.example.org\r\n",
+                0,
+                Component::Tags,
+                ErrorKind::EmptyTags,
+            ),
+            (b":nick \r\n", 6, Component::Verb, ErrorKind::InvalidVerb),
+            (
+                b"PING foo\r\nPING bar\r\n",
+                10,
+                Component::Terminator,
+                ErrorKind::TrailingData,
+            ),
+        ];
+        for &(line, offset, component, kind) in cases {
+            assert_eq!(
+                parse_message_with(line, &ParserConfig::strict()),
+                Err(ParseError::new(offset, component, kind)),
+                "{}",
+                String::from_utf8_lossy(line)
+            );
+        }
+
+        assert_eq!(
+            parse_message(b"USER user host\r\n")
+                .unwrap_err()
+                .to_string(),
+            "missing parameter in parameter 2 at byte 14"
+        );
+    }
+
+    #[test]
+    fn test_message_borrows_input() {
+        let line = b"@time=12:00 :nick!user@host PRIVMSG #foo,#bar :hello\r\n".to_vec();
+        let in_line = |text: &str| line.as_ptr_range().contains(&text.as_ptr());
+        let message = parse_message(&line).unwrap();
+        match message.command {
+            Command::Privmsg {
+                ref receivers,
+                ref message,
+            } => {
+                assert!(receivers.iter().all(|r| in_line(r)));
+                assert!(in_line(message));
+            }
+            ref other => panic!("unexpected command {:?}", other),
+        }
+        assert!(matches!(
+            message.prefix,
+            Some(Prefix::User {
+                nick: Cow::Borrowed("nick"),
+                ..
+            })
+        ));
+        assert!(matches!(
+            message.tags.get("time").unwrap().value,
+            Some(Cow::Borrowed("12:00"))
+        ));
+
+        let owned: Message<'static> = message.into_owned();
+        drop(line);
+        assert_eq!(
+            owned.command,
+            Command::Privmsg {
+                receivers: vec!["#foo".into(), "#bar".into()],
+                message: "hello".into(),
+            }
+        );
+        assert_eq!(owned.prefix.unwrap().user(), Some("user"));
+    }
+
+    #[test]
+    fn test_legacy_text() {
+        let line = b":nick PRIVMSG #caf\xe9,#bar :\x93quoted\x94\r\n";
+        let message = parse_message_with(line, &ParserConfig::lenient()).unwrap();
+        match message.command {
+            Command::Privmsg {
+                ref receivers,
+                ref message,
+            } => {
+                assert_eq!(receivers[0], "#caf\u{e9}");
+                assert_eq!(receivers[0].as_bytes(), b"#caf\xe9");
+                assert_eq!(receivers[1], "#bar");
+                assert_eq!(*message, "\u{201c}quoted\u{201d}");
+            }
+            ref other => panic!("unexpected command {:?}", other),
+        }
+        assert_eq!(message.to_bytes().unwrap(), &line[..]);
+
+        let latin1 = ParserConfig {
+            decoder: TextDecoder::Legacy(Charset::Latin1),
+            ..ParserConfig::default()
+        };
+        let message = parse_message_with(b"PRIVMSG #foo :\x93hi\r\n", &latin1).unwrap();
+        match message.command {
+            Command::Privmsg { ref message, .. } => assert_eq!(*message, "\u{93}hi"),
+            ref other => panic!("unexpected command {:?}", other),
+        }
+
+        let message =
+            parse_message_with(b"PRIVMSG #foo :caf\xe9\r\n", &ParserConfig::strict()).unwrap();
+        let mut text = String::new();
+        message.serialize(&mut text).unwrap();
+        assert_eq!(text, "PRIVMSG #foo :caf\u{fffd}\r\n");
+        assert_eq!(
+            message.to_bytes().unwrap(),
+            &b"PRIVMSG #foo :caf\xe9\r\n"[..]
+        );
+
+        // Any `fmt::Write` can be serialized to, not only the outputs of this crate.
+        struct Sink(Vec<String>);
+        impl ::std::fmt::Write for Sink {
+            fn write_str(&mut self, s: &str) -> ::std::fmt::Result {
+                self.0.push(s.to_owned());
+                Ok(())
+            }
+        }
+        let mut sink = Sink(Vec::new());
+        message.serialize(&mut sink).unwrap();
+        assert_eq!(sink.0.concat(), text);
+
+        let owned = message.into_owned();
+        assert_eq!(owned.to_bytes().unwrap(), &b"PRIVMSG #foo :caf\xe9\r\n"[..]);
+        let owned = Text::from_bytes(b"caf\xc3\xa9".to_vec(), &TextDecoder::Utf8);
+        assert_eq!(owned, "caf\u{e9}");
+        assert_eq!(owned.into_owned().as_bytes(), b"caf\xc3\xa9");
+
+        fn shout(bytes: &[u8]) -> String {
+            String::from_utf8_lossy(bytes).to_uppercase()
+        }
+        let custom = ParserConfig {
+            decoder: TextDecoder::Custom(shout),
+            ..ParserConfig::default()
+        };
+        let message = parse_message_with(b"PING hello\r\n", &custom).unwrap();
+        match message.command {
+            Command::Ping { ref server1, .. } => {
+                assert_eq!(*server1, "HELLO");
+                assert_eq!(server1.as_bytes(), b"hello");
+            }
+            ref other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let privmsg = |len: usize| {
+            let mut line = b"PRIVMSG #foo :".to_vec();
+            line.resize(len - 2, b'a');
+            line.extend_from_slice(b"\r\n");
+            line
+        };
+        assert!(parse_message(&privmsg(512)).is_ok());
+        assert_eq!(
+            parse_message(&privmsg(513)),
+            Err(ParseError::new(
+                512,
+                Component::Terminator,
+                ErrorKind::BodyTooLong
+            ))
+        );
+
+        let mut tagged = b"@a=".to_vec();
+        tagged.resize(8190, b'x');
+        tagged.extend_from_slice(b" PING x\r\n");
+        assert!(parse_message(&tagged).is_ok());
+        tagged.insert(1, b'x');
+        assert_eq!(
+            parse_message(&tagged),
+            Err(ParseError::new(0, Component::Tags, ErrorKind::TagsTooLong))
+        );
+
+        let params = |count: usize, trailing: &str| {
+            let mut line = "FOO".to_string();
+            for _ in 0..count {
+                line.push_str(" a");
+            }
+            line.push_str(trailing);
+            line.push_str("\r\n");
+            line
+        };
+        assert!(parse_message(params(15, "").as_bytes()).is_ok());
+        assert!(parse_message(params(14, " :b c").as_bytes()).is_ok());
+        for line in &[params(16, ""), params(15, " :b c")] {
+            assert_eq!(
+                parse_message(line.as_bytes()),
+                Err(ParseError::new(
+                    34,
+                    Component::Parameter(15),
+                    ErrorKind::TooManyParams
+                ))
+            );
+        }
+
+        let mut cfg = ParserConfig::default();
+        cfg.limits.body = 1024;
+        cfg.limits.params = 20;
+        assert!(parse_message_with(&privmsg(1024), &cfg).is_ok());
+        assert!(parse_message_with(params(20, "").as_bytes(), &cfg).is_ok());
+    }
+
+    #[test]
+    fn test_serialize_limits() {
+        let message = |command| Message {
+            tags: Tags::new(),
+            prefix: None,
+            command,
+        };
+
+        let long = message(Command::Privmsg {
+            receivers: vec!["#foo".into()],
+            message: "a".repeat(500).into(),
+        });
+        assert_eq!(
+            long.to_bytes_with(&Limits::default()),
+            Err(SerializeError::BodyTooLong)
+        );
+        let mut out = String::new();
+        assert_eq!(
+            long.serialize_with(&mut out, &Limits::default()),
+            Err(SerializeError::BodyTooLong)
+        );
+        assert_eq!(out, "");
+        // Without limits to hold it to, the message is written as it is.
+        assert!(long.serialize(&mut out).is_ok());
+        assert_eq!(out.len(), 516);
+        assert_eq!(long.to_bytes().unwrap().len(), 516);
+        let limits = Limits {
+            body: 1024,
+            ..Limits::default()
+        };
+        assert!(long.to_bytes_with(&limits).is_ok());
+
+        let many = message(Command::Raw {
+            verb: "FOO".into(),
+            params: vec!["a".into(); 16],
+            trailing: false,
+        });
+        assert_eq!(
+            many.to_bytes_with(&Limits::default()),
+            Err(SerializeError::TooManyParams)
+        );
+
+        let mut tagged = message(Command::Ping {
+            server1: "x".into(),
+            server2: None,
+        });
+        tagged.tags.insert("a", Some("x".repeat(9000)));
+        assert_eq!(
+            tagged.to_bytes_with(&Limits::default()),
+            Err(SerializeError::TagsTooLong)
+        );
+    }
+
+    #[test]
+    fn test_decoder_limits() {
+        let mut decoder = MessageDecoder::new();
+        decoder.feed(&[b'a'; 9000]);
+        assert_eq!(
+            decoder.decode(),
+            Some(Err(ParseError::new(
+                512,
+                Component::Verb,
+                ErrorKind::BodyTooLong
+            )))
+        );
+        assert!(decoder.buffered().is_empty());
+
+        // The rest of the overlong line is dropped once it ends.
+        decoder.feed(b"aaaa\r\nPING x\r\n");
+        assert_eq!(
+            decoder.decode(),
+            Some(Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Ping {
+                    server1: "x".into(),
+                    server2: None,
+                },
+            }))
+        );
+        assert_eq!(decoder.decode(), None);
+    }
+
+    #[test]
+    fn test_parser_config() {
+        let strict = ParserConfig::strict();
+        let lenient = ParserConfig::lenient();
+        let ping = |server1: &str| Message {
+            tags: Tags::new(),
+            prefix: None,
+            command: Command::Ping {
+                server1: server1.to_owned().into(),
+                server2: None,
+            },
+        };
+
+        assert_eq!(
+            parse_message_with(b"PING irc.example.org\n", &lenient),
+            Ok(ping("irc.example.org"))
+        );
+        assert_eq!(
+            parse_message_with(b"PING  irc.example.org\r\n", &lenient),
+            Ok(ping("irc.example.org"))
+        );
+        assert_eq!(
+            parse_message_with(b"PING  irc.example.org\r\n", &strict),
+            Err(ParseError::new(
+                5,
+                Component::Parameter(0),
+                ErrorKind::InvalidSyntax
+            ))
+        );
+        assert_eq!(
+            parse_message_with(b"PING irc.example.org \r\n", &lenient),
+            Ok(ping("irc.example.org"))
+        );
+        assert_eq!(
+            parse_message_with(b"PING irc.example.org \r\n", &strict),
+            Err(ParseError::new(
+                20,
+                Component::Terminator,
+                ErrorKind::TrailingSpace
+            ))
+        );
+        assert_eq!(
+            parse_message_with(b"PING irc.exam\0ple.org\r\n", &lenient),
+            Ok(ping("irc.example.org"))
+        );
+        assert_eq!(
+            parse_message_with(b"PING irc.exam\0ple.org\r\n", &strict),
+            Err(ParseError::new(
+                13,
+                Component::Parameter(0),
+                ErrorKind::NulByte
+            ))
+        );
+
+        // The default only relaxes the spacing between components.
+        assert_eq!(
+            parse_message(b"PING  irc.example.org\r\n"),
+            Ok(ping("irc.example.org"))
+        );
+        for &(line, offset, component, kind) in &[
+            (
+                &b"PING irc.example.org\n"[..],
+                20,
+                Component::Terminator,
+                ErrorKind::InvalidTerminator,
+            ),
+            (
+                b"PING irc.example.org \r\n",
+                20,
+                Component::Terminator,
+                ErrorKind::TrailingSpace,
+            ),
+            (
+                b"PING irc.exam\0ple.org\r\n",
+                13,
+                Component::Parameter(0),
+                ErrorKind::NulByte,
+            ),
+        ] {
+            assert_eq!(
+                parse_message(line),
+                Err(ParseError::new(offset, component, kind))
+            );
+        }
+        match parse_message(b"PING caf\xe9\r\n").unwrap().command {
+            Command::Ping { ref server1, .. } => assert_eq!(*server1, "caf\u{fffd}"),
+            ref other => panic!("unexpected command {:?}", other),
+        }
+
+        let mut strict_decoder = MessageDecoder::with_config(strict);
+        strict_decoder.feed(b"PING a\nPING b\r\n");
+        assert_eq!(
+            strict_decoder.decode(),
+            Some(Err(ParseError::new(
+                6,
+                Component::Terminator,
+                ErrorKind::InvalidTerminator
+            )))
+        );
+        assert_eq!(strict_decoder.decode(), None);
+
+        let mut lenient_decoder = MessageDecoder::with_config(lenient);
+        lenient_decoder.feed(b"PING a\nPING b\r\n");
+        assert_eq!(lenient_decoder.decode(), Some(Ok(ping("a"))));
+        assert_eq!(lenient_decoder.decode(), Some(Ok(ping("b"))));
+        assert_eq!(lenient_decoder.decode(), None);
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder = MessageDecoder::new();
+        assert_eq!(decoder.decode(), None);
+
+        decoder.feed(b"PING irc.exa");
+        assert_eq!(decoder.decode(), None);
+        decoder.feed(b"mple.org\r");
+        assert_eq!(decoder.decode(), None);
+        decoder.feed(b"\nPING :foo bar\r\nPI");
+        assert_eq!(decoder.buffered(), b"PI");
+
+        assert_eq!(
+            decoder.decode(),
+            Some(Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Ping {
+                    server1: "irc.example.org".into(),
+                    server2: None,
+                },
+            }))
+        );
+        assert_eq!(
+            decoder.decode(),
+            Some(Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Ping {
+                    server1: "foo bar".into(),
+                    server2: None,
+                },
+            }))
+        );
+        assert_eq!(decoder.decode(), None);
+        assert_eq!(decoder.buffered(), b"PI");
+
+        // A complete line without a command is malformed, not partial.
+        decoder.feed(b"NG x\r\n:nick\r\n");
+        assert!(decoder.decode().unwrap().is_ok());
+        assert_eq!(
+            decoder.decode(),
+            Some(Err(ParseError::new(
+                5,
+                Component::Verb,
+                ErrorKind::InvalidVerb
+            )))
+        );
+    }
+
+    #[test]
+    fn test_decoder_recovers_after_malformed_line() {
+        let mut decoder = MessageDecoder::new();
+        decoder.feed(b"USER foo\r\nQUIT\r\n");
+
+        assert_eq!(
+            decoder.decode(),
+            Some(Err(ParseError::new(
+                8,
+                Component::Parameter(1),
+                ErrorKind::MissingParameter
+            )))
+        );
+        assert_eq!(
+            decoder.decode(),
+            Some(Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Quit { message: None },
+            }))
+        );
+        assert_eq!(decoder.decode(), None);
+    }
+
+    #[test]
+    fn test_decoder_read_message() {
+        struct Trickle<'a>(&'a [u8]);
+
+        impl<'a> std::io::Read for Trickle<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() || buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.0[0];
+                self.0 = &self.0[1..];
+                Ok(1)
+            }
+        }
+
+        let mut reader = Trickle(b"AWAY\r\nAWAY :gone\r\nAWAY");
+        let mut decoder = MessageDecoder::new();
+
+        let message = decoder.read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message.unwrap().command, Command::Away { message: None });
+        let message = decoder.read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            message.unwrap().command,
+            Command::Away {
+                message: Some("gone".into())
+            }
+        );
+        let message = decoder.read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(message.unwrap_err().kind(), ErrorKind::Incomplete);
+        assert!(decoder.read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_message_tags() {
+        let message = parse_message(
+            b"@time=2018-06-01T12:00:00.000Z;+example.com/note=a\\sb\\:c\\\\d;+typing :nick PRIVMSG #foo :bar\r\n",
+        ).unwrap();
+
+        assert_eq!(message.tags.len(), 3);
+        assert_eq!(message.tags.value("time"), Some("2018-06-01T12:00:00.000Z"));
+        assert_eq!(message.tags.value("+example.com/note"), Some("a b;c\\d"));
+        assert_eq!(message.tags.value("+typing"), Some(""));
+        assert_eq!(message.tags.get("+typing").unwrap().value, None);
+        assert_eq!(message.tags.value("msgid"), None);
+
+        let note = message.tags.get("+example.com/note").unwrap();
+        assert!(note.is_client_only());
+        assert_eq!(note.vendor(), Some("example.com"));
+        assert_eq!(note.name(), "note");
+
+        let time = message.tags.get("time").unwrap();
+        assert!(!time.is_client_only());
+        assert_eq!(time.vendor(), None);
+        assert_eq!(time.name(), "time");
+    }
+
+    #[test]
+    fn test_message_tags_round_trip() {
+        assert_round_trip(&[
+            b"@a=b\\sc;+d/e=\\:\\r\\n\\\\;f;g= PING irc.example.org\r\n",
+            b"@msgid=abc :nick!user@host PRIVMSG #foo :bar baz\r\n",
+        ]);
+    }
+
+    #[test]
+    fn numeric() {
+        assert_eq!(
+            command_numeric(
+                b"001 nick :Welcome to the network\r\n",
+                &ParserConfig::default()
+            ),
             Ok((
-                &b""[..],
-                Message {
-                    prefix: None,
-                    command: Command::Whois {
-                        server: None,
-                        nickmasks: vec!["kyrias".into(), "demize".into()],
-                    },
+                &b"\r\n"[..],
+                Command::Numeric {
+                    code: Reply::RplWelcome,
+                    params: vec!["nick".into(), "Welcome to the network".into()],
+                    trailing: true,
                 }
             ))
         );
         assert_eq!(
-            parse_message(b"WHOIS chat.freenode.net kyrias,demize\r\n"),
+            command_numeric(
+                b"353 nick = #foo :@nick +other\r\n",
+                &ParserConfig::default()
+            ),
             Ok((
-                &b""[..],
-                Message {
-                    prefix: None,
-                    command: Command::Whois {
-                        server: Some("chat.freenode.net".into()),
-                        nickmasks: vec!["kyrias".into(), "demize".into()],
-                    },
+                &b"\r\n"[..],
+                Command::Numeric {
+                    code: Reply::RplNamReply,
+                    params: vec![
+                        "nick".into(),
+                        "=".into(),
+                        "#foo".into(),
+                        "@nick +other".into(),
+                    ],
+                    trailing: true,
                 }
             ))
         );
+        assert_eq!(
+            command_numeric(b"999\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Numeric {
+                    code: Reply::Unknown(999),
+                    params: vec![],
+                    trailing: false,
+                }
+            ))
+        );
+        assert!(command_numeric(b"12a nick\r\n", &ParserConfig::default()).is_err());
+        assert!(command_numeric(b"0012 nick\r\n", &ParserConfig::default()).is_err());
+
+        assert_round_trip(&[
+            b":irc.example.org 001 nick :Welcome\r\n",
+            b":irc.example.org 005 nick CHANTYPES=# PREFIX=(ov)@+ :are supported by this server\r\n",
+            b":irc.example.org 221 nick +i\r\n",
+            b":irc.example.org 353 nick = #foo :@nick\r\n",
+            b":irc.example.org 366 nick #foo :End of /NAMES list.\r\n",
+            b"999\r\n",
+        ]);
+    }
+
+    #[test]
+    fn raw() {
+        assert_eq!(
+            command(b"CAP * LS :multi-prefix sasl\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Raw {
+                    verb: "CAP".into(),
+                    params: vec!["*".into(), "LS".into(), "multi-prefix sasl".into()],
+                    trailing: true,
+                }
+            ))
+        );
+        assert_eq!(
+            command(b"AUTHENTICATE  +\r\n", &ParserConfig::default()),
+            Ok((
+                &b"\r\n"[..],
+                Command::Raw {
+                    verb: "AUTHENTICATE".into(),
+                    params: vec!["+".into()],
+                    trailing: false,
+                }
+            ))
+        );
+        assert!(command(b"0012\r\n", &ParserConfig::default()).is_err());
+        assert_eq!(
+            parse_message(b"FOO-BAR x\r\n"),
+            Err(ParseError::new(0, Component::Verb, ErrorKind::InvalidVerb))
+        );
+
+        // A tag section must be followed by a command, not mistaken for one.
+        assert_eq!(
+            parse_message(b"@a=b\r\n"),
+            Err(ParseError::new(4, Component::Verb, ErrorKind::InvalidVerb))
+        );
+        assert_eq!(
+            Command::from_parts("@a=b", &[]),
+            Err(ParseError::new(0, Component::Verb, ErrorKind::InvalidVerb))
+        );
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        assert_round_trip(&[
+            b"CAP * LS :multi-prefix sasl\r\n",
+            b":nick!user@host CHGHOST user new.host\r\n",
+            b"BATCH +abc chathistory #foo\r\n",
+            b"FOO :\r\n",
+            b"FOO\r\n",
+            b"FOO a :bar\r\n",
+            b":irc.example.org FOO :bar\r\n",
+        ]);
+    }
+
+    #[test]
+    fn test_case_insensitive_verbs() {
+        let cases: &[(&[u8], &[u8])] = &[
+            (b"privmsg #foo :hi\r\n", b"PRIVMSG #foo :hi\r\n"),
+            (b"Join #foo :key\r\n", b"JOIN #foo :key\r\n"),
+            (b"mode #foo +nt\r\n", b"MODE #foo +nt\r\n"),
+            (b"links\r\n", b"LINKS\r\n"),
+            (b"userhost a b\r\n", b"USERHOST a b\r\n"),
+            (b"001 nick :Welcome home\r\n", b"001 nick :Welcome home\r\n"),
+            (
+                b"cap * LS :sasl multi-prefix\r\n",
+                b"CAP * LS :sasl multi-prefix\r\n",
+            ),
+        ];
+        for &(line, canonical) in cases {
+            assert_eq!(parse_message(line), parse_message(canonical));
+            assert_eq!(parse_message(line).unwrap().to_bytes().unwrap(), canonical);
+        }
+
+        let raw = Command::Raw {
+            verb: "chghost".into(),
+            params: vec!["user".into(), "new.host".into()],
+            trailing: false,
+        };
+        let mut out = String::new();
+        raw.serialize(&mut out).unwrap();
+        assert_eq!(out, "CHGHOST user new.host");
+    }
+
+    #[test]
+    fn test_command_parts() {
+        let command = Command::Privmsg {
+            receivers: vec!["#foo".into(), "#bar".into()],
+            message: "hello there".into(),
+        };
+        assert_eq!(command.verb(), "PRIVMSG");
+        assert_eq!(
+            command.params(),
+            vec![Text::from("#foo,#bar"), "hello there".into()]
+        );
+
+        let command = Command::Raw {
+            verb: "chghost".into(),
+            params: vec!["user".into(), "new.host".into()],
+            trailing: false,
+        };
+        assert_eq!(command.verb(), "CHGHOST");
+        assert_eq!(
+            command.params(),
+            vec![Text::from("user"), "new.host".into()]
+        );
+
+        let lines: &[&[u8]] = &[
+            b"NICK nick 3\r\n",
+            b"USER user host server :real name\r\n",
+            b"JOIN #foo,#bar key\r\n",
+            b"JOIN #foo\r\n",
+            b"JOIN 0\r\n",
+            b"PART #foo,#bar :see you later\r\n",
+            b"MODE #foo +ov-b nick other *!*@host\r\n",
+            b"MODE #foo\r\n",
+            b"MODE #foo +e\r\n",
+            b"MODE nick +iws-x +cC\r\n",
+            b"LIST\r\n",
+            b"KICK #foo nick :bye now\r\n",
+            b"KICK #foo,#bar nick,other :bye now\r\n",
+            b"NOTICE a,b :text here\r\n",
+            b"WHOIS irc.example.org nick,other\r\n",
+            b"USERHOST a b c\r\n",
+            b"REHASH\r\n",
+            b"001 nick :Welcome home\r\n",
+            b"CAP * LS :sasl multi-prefix\r\n",
+        ];
+        for line in lines {
+            let command = parse_message(line).unwrap().command;
+            assert_eq!(
+                Command::from_parts(&command.verb(), &command.params()),
+                Ok(command),
+                "{}",
+                String::from_utf8_lossy(line)
+            );
+        }
+    }
+
+    #[test]
+    fn test_command_from_parts_errors() {
+        assert_eq!(
+            Command::from_parts("PRIVMSG", &["#foo".into()]),
+            Err(ParseError::new(
+                12,
+                Component::Parameter(1),
+                ErrorKind::MissingParameter
+            ))
+        );
+        assert_eq!(
+            Command::from_parts("", &[]),
+            Err(ParseError::new(0, Component::Verb, ErrorKind::InvalidVerb))
+        );
+        assert_eq!(
+            Command::from_parts("PRIVMSG", &["#foo bar".into(), "hi".into()]),
+            Err(ParseError::new(
+                12,
+                Component::Parameter(0),
+                ErrorKind::InvalidSyntax
+            ))
+        );
+        assert_eq!(
+            Command::from_parts("NICK", &["nick\r\nQUIT".into()]),
+            Err(ParseError::new(
+                9,
+                Component::Parameter(0),
+                ErrorKind::InvalidSyntax
+            ))
+        );
+        assert_eq!(
+            Command::from_parts("SERVER", &["foo".into(), "x".into(), "info".into()]),
+            Err(ParseError::new(
+                11,
+                Component::Parameter(1),
+                ErrorKind::InvalidNumber
+            ))
+        );
+        assert_eq!(
+            Command::from_parts("FOO", &vec!["a".into(); 16]).map(|_| ()),
+            Err(ParseError::new(
+                34,
+                Component::Parameter(15),
+                ErrorKind::TooManyParams
+            ))
+        );
+    }
+
+    #[test]
+    fn test_reply() {
+        assert_eq!(Reply::from(433), Reply::ErrNicknameInUse);
+        assert_eq!(Reply::ErrNicknameInUse.code(), 433);
+        assert_eq!(Reply::ErrNicknameInUse.name(), Some("ERR_NICKNAMEINUSE"));
+        assert_eq!(Reply::from(217).name(), Some("RPL_STATSQLINE"));
+        assert_eq!(Reply::from(999), Reply::Unknown(999));
+        assert_eq!(Reply::Unknown(999).name(), None);
+    }
+
+    #[test]
+    fn test_numeric_round_trip() {
+        assert_round_trip(&[
+            b":irc.example.org 001 nick :Welcome to the network\r\n",
+            b":irc.example.org 433 * nick :Nickname is already in use\r\n",
+            b":irc.example.org 324 nick #foo +nt\r\n",
+            b":irc.example.org 005 nick CHANTYPES=# :are supported by this server\r\n",
+        ]);
+    }
+
+    #[test]
+    fn test_whois() {
+        assert_eq!(
+            parse_message(b"WHO kyrias\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Who {
+                    name: None,
+                    o: Some("kyrias".into())
+                }
+            })
+        );
+        assert_eq!(
+            parse_message(b"WHO kyrias foo\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Who {
+                    name: Some("kyrias".into()),
+                    o: Some("foo".into())
+                }
+            })
+        );
+        assert_eq!(
+            parse_message(b"WHOIS kyrias\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Whois {
+                    server: None,
+                    nickmasks: vec!["kyrias".into()],
+                },
+            })
+        );
+        assert_eq!(
+            parse_message(b"WHOIS kyrias,demize\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Whois {
+                    server: None,
+                    nickmasks: vec!["kyrias".into(), "demize".into()],
+                },
+            })
+        );
+        assert_eq!(
+            parse_message(b"WHOIS chat.freenode.net kyrias,demize\r\n"),
+            Ok(Message {
+                tags: Tags::new(),
+                prefix: None,
+                command: Command::Whois {
+                    server: Some("chat.freenode.net".into()),
+                    nickmasks: vec!["kyrias".into(), "demize".into()],
+                },
+            })
+        );
     }
 }
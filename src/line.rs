@@ -0,0 +1,125 @@
+/// A component of a line, as split up by `tokens`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) enum Part {
+    Tags,
+    Prefix,
+    Verb,
+    /// The zero-based index of the parameter following the verb.
+    Param(usize),
+    /// Where the line terminator starts, or the end of a line without one.
+    End,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) struct Token {
+    pub part: Part,
+    /// The first byte of the component, after the spaces in front of it.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits `line` into its components at the spaces between them, the only other thing looked at
+/// being the `@` and `:` that start tags, a prefix and a trailing parameter.
+///
+/// This works on partial and malformed lines alike, so that limits can be checked before anything
+/// is allocated for a line and parse errors can be put down to a component.
+pub(crate) fn tokens(line: &[u8]) -> Tokens<'_> {
+    Tokens {
+        line,
+        pos: 0,
+        state: State::Tags,
+    }
+}
+
+pub(crate) struct Tokens<'a> {
+    line: &'a [u8],
+    pos: usize,
+    state: State,
+}
+
+// What `Tokens` looks for next.
+#[derive(Clone, Copy)]
+enum State {
+    Tags,
+    Prefix,
+    Verb,
+    Param(usize),
+    Done,
+}
+
+fn is_end(c: &u8) -> bool {
+    b"\r\n".contains(c)
+}
+
+impl<'a> Tokens<'a> {
+    fn token_end(&self, from: usize) -> usize {
+        from + self.line[from..]
+            .iter()
+            .position(|c| *c == b' ' || is_end(c))
+            .unwrap_or(self.line.len() - from)
+    }
+
+    fn skip_spaces(&self, from: usize) -> usize {
+        from + self.line[from..].iter().take_while(|c| **c == b' ').count()
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let line = self.line;
+        loop {
+            let (part, start, end) = match self.state {
+                State::Tags => {
+                    self.state = State::Prefix;
+                    if line.first() != Some(&b'@') {
+                        continue;
+                    }
+                    (Part::Tags, 0, self.token_end(0))
+                }
+                State::Prefix => {
+                    self.state = State::Verb;
+                    if line.get(self.pos) != Some(&b':') {
+                        continue;
+                    }
+                    (Part::Prefix, self.pos, self.token_end(self.pos))
+                }
+                // The verb is there even when it is empty, so that a line always has one.
+                State::Verb => {
+                    self.state = State::Param(0);
+                    (Part::Verb, self.pos, self.token_end(self.pos))
+                }
+                State::Param(idx) => {
+                    let start = self.skip_spaces(self.pos);
+                    match line.get(start) {
+                        Some(c) if !is_end(c) => {
+                            self.state = State::Param(idx + 1);
+                            let end = if *c == b':' {
+                                start
+                                    + line[start..]
+                                        .iter()
+                                        .position(is_end)
+                                        .unwrap_or(line.len() - start)
+                            } else {
+                                self.token_end(start)
+                            };
+                            (Part::Param(idx), start, end)
+                        }
+                        _ => {
+                            self.state = State::Done;
+                            (Part::End, start, line.len())
+                        }
+                    }
+                }
+                State::Done => return None,
+            };
+            self.pos = match part {
+                // Spaces are only skipped before the verb when it follows tags or a prefix.
+                Part::Tags | Part::Prefix => self.skip_spaces(end),
+                _ => end,
+            };
+            return Some(Token { part, start, end });
+        }
+    }
+}
@@ -1,268 +1,437 @@
 use nom;
 
-use argument_maybe_last;
+use {argument_maybe_last, spaces, Component, ErrorKind, ParseError, ParserConfig};
+
+// Channel modes: https://tools.ietf.org/html/rfc2811#section-4
+
+//
+// MODE TABLE
+//
+
+/// How a channel mode takes its argument, following the groups of the RPL_ISUPPORT `CHANMODES`
+/// and `PREFIX` tokens.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ChannelModeKind {
+    /// Type A: adds to or removes from a list, always with an argument.
+    List,
+    /// Type B: a setting that always has an argument.
+    Parameter,
+    /// Type C: a setting that only has an argument when it is set.
+    ParameterWhenSet,
+    /// Type D: a flag, which never has an argument.
+    Flag,
+    /// A membership mode such as op or voice, which always has a nickname.
+    Prefix,
+}
 
-/// Channel modes: https://tools.ietf.org/html/rfc2811#section-4
+impl ChannelModeKind {
+    fn takes_argument(self, adding: bool) -> bool {
+        match self {
+            ChannelModeKind::List | ChannelModeKind::Parameter | ChannelModeKind::Prefix => true,
+            ChannelModeKind::ParameterWhenSet => adding,
+            ChannelModeKind::Flag => false,
+        }
+    }
+}
 
+/// The channel modes a server supports, which decide how a mode string is split into modes and
+/// their arguments.
 ///
-/// VALUELESS
-///
+/// The default describes RFC 2811 together with the widespread quiet list `q`, as if the server
+/// had sent `CHANMODES=beIq,k,l,imnpst`, `PREFIX=(ov)@+` and `MODES=3`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ChannelModeTable {
+    // The A, B, C and D groups of CHANMODES.
+    list: String,
+    parameter: String,
+    parameter_when_set: String,
+    flag: String,
+    // The mode letters of PREFIX, each with the symbol shown before nicknames.
+    prefixes: Vec<(char, char)>,
+    max_modes: usize,
+}
 
-#[derive(Debug)]
-enum ValueLessChannelMode {
-    Op,
-    Voice,
+impl Default for ChannelModeTable {
+    fn default() -> ChannelModeTable {
+        ChannelModeTable::new("beIq,k,l,imnpst", "(ov)@+")
+    }
+}
 
-    //Anonymous,
-    InviteOnly,
-    Moderated,
-    NoExternal,
-    Quiet,
-    Private,
-    Secret,
-    //Reop,
-    OpsTopic,
+impl ChannelModeTable {
+    /// Builds a table from the values of the `CHANMODES` and `PREFIX` tokens, such as
+    /// `"beI,k,l,imnpst"` and `"(qaohv)~&@%+"`.
+    pub fn new(chanmodes: &str, prefix: &str) -> ChannelModeTable {
+        let mut table = ChannelModeTable {
+            list: String::new(),
+            parameter: String::new(),
+            parameter_when_set: String::new(),
+            flag: String::new(),
+            prefixes: Vec::new(),
+            max_modes: 3,
+        };
+        table.set_chanmodes(chanmodes);
+        table.set_prefix(prefix);
+        table
+    }
 
-    Key,
-    Limit,
+    /// Replaces the mode groups with those of a `CHANMODES` value. Groups past the fourth are
+    /// ignored, as the specification asks.
+    pub fn set_chanmodes(&mut self, value: &str) {
+        let mut groups = value.split(',').map(str::to_owned);
+        self.list = groups.next().unwrap_or_default();
+        self.parameter = groups.next().unwrap_or_default();
+        self.parameter_when_set = groups.next().unwrap_or_default();
+        self.flag = groups.next().unwrap_or_default();
+    }
 
-    Ban,
-    BanException,
-    InviteException,
-}
+    /// Replaces the membership modes with those of a `PREFIX` value. An empty or malformed value
+    /// leaves none.
+    pub fn set_prefix(&mut self, value: &str) {
+        self.prefixes = value
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .filter(|&(modes, symbols)| modes.chars().count() == symbols.chars().count())
+            .map(|(modes, symbols)| modes.chars().zip(symbols.chars()).collect())
+            .unwrap_or_default();
+    }
 
-impl ValueLessChannelMode {
-    fn parse(input: &[u8]) -> nom::IResult<&[u8], ValueLessChannelMode> {
-        let (remaining, c) = try_parse!(input, nom::anychar);
-        let vlm = match c {
-            'o' => ValueLessChannelMode::Op,
-            'v' => ValueLessChannelMode::Voice,
-
-            'i' => ValueLessChannelMode::InviteOnly,
-            'm' => ValueLessChannelMode::Moderated,
-            'n' => ValueLessChannelMode::NoExternal,
-            'q' => ValueLessChannelMode::Quiet,
-            'p' => ValueLessChannelMode::Private,
-            's' => ValueLessChannelMode::Secret,
-            't' => ValueLessChannelMode::OpsTopic,
-
-            'k' => ValueLessChannelMode::Key,
-            'l' => ValueLessChannelMode::Limit,
-
-            'b' => ValueLessChannelMode::Ban,
-            'e' => ValueLessChannelMode::BanException,
-            'I' => ValueLessChannelMode::InviteException,
-            _ => Err(nom::Err::Error(nom::Context::Code(
-                input,
-                nom::ErrorKind::Custom(1),
-            )))?,
-        };
-        Ok((remaining, vlm))
+    /// Applies a single RPL_ISUPPORT token such as `CHANMODES=beI,k,l,imnpst`. Tokens other than
+    /// `CHANMODES`, `PREFIX` and `MODES` are ignored, so every parameter of the reply can be passed
+    /// in.
+    pub fn apply_isupport(&mut self, token: &str) {
+        let (key, value) = token.split_once('=').unwrap_or((token, ""));
+        match key {
+            "CHANMODES" => self.set_chanmodes(value),
+            "PREFIX" => self.set_prefix(value),
+            // Without a value there is no limit.
+            "MODES" => self.max_modes = value.parse().unwrap_or(usize::MAX),
+            _ => {}
+        }
+    }
+
+    /// How `mode` takes its argument, or `None` if the server does not advertise it.
+    pub fn kind(&self, mode: char) -> Option<ChannelModeKind> {
+        if self.prefixes.iter().any(|&(m, _)| m == mode) {
+            Some(ChannelModeKind::Prefix)
+        } else if self.list.contains(mode) {
+            Some(ChannelModeKind::List)
+        } else if self.parameter.contains(mode) {
+            Some(ChannelModeKind::Parameter)
+        } else if self.parameter_when_set.contains(mode) {
+            Some(ChannelModeKind::ParameterWhenSet)
+        } else if self.flag.contains(mode) {
+            Some(ChannelModeKind::Flag)
+        } else {
+            None
+        }
+    }
+
+    /// How many modes with an argument the server accepts in a single MODE command.
+    pub fn max_modes(&self) -> usize {
+        self.max_modes
     }
-}
 
-#[derive(Debug)]
-enum ValueLessChannelModeChange {
-    Added(ValueLessChannelMode),
-    Removed(ValueLessChannelMode),
+    /// The membership modes with their symbols, from the highest rank down.
+    pub fn prefixes(&self) -> &[(char, char)] {
+        &self.prefixes
+    }
 }
 
-impl ValueLessChannelModeChange {
-    fn partial(input: &[u8]) -> nom::IResult<&[u8], Vec<ValueLessChannelModeChange>> {
-        let (remaining, status) = try_parse!(input, one_of!(b"+-"));
-        match status {
-            '+' => {
-                let (remaining, modes) = try_parse!(remaining, many1!(ValueLessChannelMode::parse));
-                let vlmcs = modes
-                    .into_iter()
-                    .map(|m| ValueLessChannelModeChange::Added(m))
-                    .collect();
-                Ok((remaining, vlmcs))
+//
+// PARSING
+//
+
+/// Parses the signs and letters of a mode string, without any arguments. Mode letters are ASCII
+/// letters, and every sign must be followed by at least one.
+fn mode_letters(input: &[u8]) -> nom::IResult<&[u8], Vec<(bool, char)>> {
+    let invalid =
+        |at| nom::Err::Failure(nom::Context::Code(at, ErrorKind::InvalidModeString.code()));
+    try_parse!(
+        input,
+        return_error!(ErrorKind::MissingModeSign.code(), peek!(one_of!(b"+-")))
+    );
+    let mut remaining = input;
+    let mut adding = true;
+    let mut letters = Vec::new();
+    // The last sign, for as long as no letter has followed it.
+    let mut bare_sign = None;
+    loop {
+        match remaining.first() {
+            Some(&c) if c == b'+' || c == b'-' => {
+                if bare_sign.is_some() {
+                    return Err(invalid(remaining));
+                }
+                adding = c == b'+';
+                bare_sign = Some(remaining);
             }
-            '-' => {
-                let (remaining, modes) = try_parse!(remaining, many1!(ValueLessChannelMode::parse));
-                let vlmcs = modes
-                    .into_iter()
-                    .map(|m| ValueLessChannelModeChange::Removed(m))
-                    .collect();
-                Ok((remaining, vlmcs))
+            Some(c) if c.is_ascii_alphabetic() => {
+                letters.push((adding, char::from(*c)));
+                bare_sign = None;
             }
-            _ => Err(nom::Err::Error(nom::Context::Code(
-                input,
-                nom::ErrorKind::Custom(2),
-            )))?,
+            Some(c) if !b" \0\r\n".contains(c) => return Err(invalid(remaining)),
+            _ => break,
         }
+        remaining = &remaining[1..];
     }
+    match bare_sign {
+        Some(sign) => Err(invalid(sign)),
+        None => Ok((remaining, letters)),
+    }
+}
 
-    fn parse(input: &[u8]) -> nom::IResult<&[u8], Vec<ValueLessChannelModeChange>> {
-        let (remaining, vlmcs) = try_parse!(input, many1!(ValueLessChannelModeChange::partial));
-        Ok((remaining, vlmcs.into_iter().flat_map(|mcp| mcp).collect()))
+named_args!(mode_argument<'a>(cfg: &ParserConfig)<&'a [u8], String>,
+    do_parse!(
+        call!(spaces, cfg) >>
+        arg: call!(argument_maybe_last, cfg) >>
+        (arg.as_str().to_owned())
+    )
+);
+
+/// An argument that is not empty and has no spaces, commas or control characters.
+fn is_word(arg: &str) -> bool {
+    !arg.is_empty() && !arg.contains(|c: char| c == ' ' || c == ',' || c.is_control())
+}
+
+/// A mask such as `nick!*@*.example.org`. A bare nickname or host, which servers expand, and
+/// extended bans such as `$a:account` are accepted too.
+fn is_mask(arg: &str) -> bool {
+    is_word(arg)
+        && match arg.find('!') {
+            Some(bang) => !arg[bang + 1..].contains('!') && arg[bang + 1..].contains('@'),
+            None => true,
+        }
+}
+
+/// Checks the argument of `mode`, giving the reason it is malformed if it is.
+fn check_argument(mode: char, kind: ChannelModeKind, arg: &str) -> Result<(), ErrorKind> {
+    let (valid, reason) = match (kind, mode) {
+        (ChannelModeKind::List, _) => (is_mask(arg), ErrorKind::InvalidMask),
+        (ChannelModeKind::Prefix, _) => (is_word(arg), ErrorKind::InvalidModeArgument),
+        (_, 'l') => (
+            arg.bytes().all(|b| b.is_ascii_digit()) && arg.parse::<u64>().is_ok(),
+            ErrorKind::InvalidLimit,
+        ),
+        (_, 'k') => (is_word(arg), ErrorKind::InvalidKey),
+        (_, _) => (is_word(arg), ErrorKind::InvalidModeArgument),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(reason)
     }
 }
 
-///
-/// VALUED
-///
+/// Parses the argument of `mode`, failing at its start if it is malformed. An empty argument is an
+/// error rather than a failure, as if it were missing.
+fn checked_argument<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+    mode: char,
+    kind: ChannelModeKind,
+) -> nom::IResult<&'a [u8], String> {
+    let (start, _) = spaces(input, cfg)?;
+    let (rest, arg) = argument_maybe_last(start, cfg)?;
+    let arg = arg.as_str().to_owned();
+    match check_argument(mode, kind, &arg) {
+        Ok(()) => Ok((rest, arg)),
+        // An empty trailing parameter is no argument at all, which the caller may accept.
+        Err(reason) if arg.is_empty() => {
+            Err(nom::Err::Error(nom::Context::Code(start, reason.code())))
+        }
+        Err(reason) => Err(nom::Err::Failure(nom::Context::Code(start, reason.code()))),
+    }
+}
 
+/// A channel mode being set.
+///
+/// Besides the modes of RFC 2811, the variants cover modes that are common to the widely deployed
+/// servers. Where networks give a letter different meanings, the variant is chosen by how the
+/// `ChannelModeTable` says the letter takes its argument: `q` is `Quiet` as a list mode and
+/// `Owner` as a membership mode, and `a` is `Admin` as a membership mode and `Anonymous` otherwise.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum AddedChannelMode {
     Op(String),
     Voice(String),
-
+    /// `h`, shown as `%`.
+    HalfOp(String),
+    /// `a`, shown as `&`.
+    Admin(String),
+    /// `q`, shown as `~`.
+    Owner(String),
+
+    Anonymous,
     InviteOnly,
     Moderated,
     NoExternal,
     Quiet(String),
     Private,
     Secret,
+    Reop,
     OpsTopic,
 
+    /// `c`: messages with colour codes are blocked.
+    NoColors,
+    /// `C`: CTCPs other than ACTION are blocked.
+    NoCtcp,
+    /// `R`: only users logged in to an account may join.
+    RegisteredOnly,
+    /// `S`: colour codes are stripped from messages.
+    StripColors,
+    /// `z`: only users connected over TLS may join.
+    SecureOnly,
+    /// `Q`: users may not be kicked.
+    NoKicks,
+
     Key(String),
     Limit(u64),
+    /// `j`: how many users may join in how many seconds, such as `3:10`.
+    JoinThrottle(String),
+    /// `f`: the flood protection settings, whose format depends on the server.
+    Flood(String),
+    /// `L`: the channel users are sent to when this one is full.
+    Link(String),
 
     Ban(String),
     BanException(String),
     InviteException(String),
+
+    /// A mode without a variant of its own, or one used in a way its variant cannot hold.
+    Other(char, Option<String>),
 }
 
 impl AddedChannelMode {
-    fn from_valueless(
-        input: &[u8],
-        vlm: ValueLessChannelMode,
-    ) -> nom::IResult<&[u8], AddedChannelMode> {
-        named!(mode_argument<&[u8], String>,
-           do_parse!(
-               tag!(" ") >>
-               arg: argument_maybe_last >>
-               (arg)
-           )
-        );
-
-        match vlm {
-            ValueLessChannelMode::Op => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::Op(arg.to_string())))
-            }
-            ValueLessChannelMode::Voice => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::Voice(arg.to_string())))
-            }
-
-            ValueLessChannelMode::InviteOnly => Ok((input, AddedChannelMode::InviteOnly)),
-            ValueLessChannelMode::Moderated => Ok((input, AddedChannelMode::Moderated)),
-            ValueLessChannelMode::NoExternal => Ok((input, AddedChannelMode::NoExternal)),
-            ValueLessChannelMode::Quiet => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::Quiet(arg.to_string())))
-            }
-            ValueLessChannelMode::Private => Ok((input, AddedChannelMode::Private)),
-            ValueLessChannelMode::Secret => Ok((input, AddedChannelMode::Secret)),
-            ValueLessChannelMode::OpsTopic => Ok((input, AddedChannelMode::OpsTopic)),
-
-            ValueLessChannelMode::Key => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::Key(arg.to_string())))
-            }
-            ValueLessChannelMode::Limit => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((
-                    remaining,
-                    AddedChannelMode::Limit(u64::from_str_radix(&arg, 10).expect("user limit")),
-                ))
-            }
-
-            ValueLessChannelMode::Ban => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::Ban(arg.to_string())))
-            }
-            ValueLessChannelMode::BanException => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, AddedChannelMode::BanException(arg.to_string())))
-            }
-            ValueLessChannelMode::InviteException => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((
-                    remaining,
-                    AddedChannelMode::InviteException(arg.to_string()),
-                ))
-            }
+    pub(crate) fn new(mode: char, kind: ChannelModeKind, arg: Option<String>) -> AddedChannelMode {
+        match (mode, kind, arg) {
+            ('o', _, Some(nick)) => AddedChannelMode::Op(nick),
+            ('v', _, Some(nick)) => AddedChannelMode::Voice(nick),
+            ('h', _, Some(nick)) => AddedChannelMode::HalfOp(nick),
+            ('a', ChannelModeKind::Prefix, Some(nick)) => AddedChannelMode::Admin(nick),
+            ('q', ChannelModeKind::Prefix, Some(nick)) => AddedChannelMode::Owner(nick),
+
+            ('a', _, None) => AddedChannelMode::Anonymous,
+            ('i', _, None) => AddedChannelMode::InviteOnly,
+            ('m', _, None) => AddedChannelMode::Moderated,
+            ('n', _, None) => AddedChannelMode::NoExternal,
+            ('q', ChannelModeKind::List, Some(mask)) => AddedChannelMode::Quiet(mask),
+            ('p', _, None) => AddedChannelMode::Private,
+            ('s', _, None) => AddedChannelMode::Secret,
+            ('r', _, None) => AddedChannelMode::Reop,
+            ('t', _, None) => AddedChannelMode::OpsTopic,
+
+            ('c', _, None) => AddedChannelMode::NoColors,
+            ('C', _, None) => AddedChannelMode::NoCtcp,
+            ('R', _, None) => AddedChannelMode::RegisteredOnly,
+            ('S', _, None) => AddedChannelMode::StripColors,
+            ('z', _, None) => AddedChannelMode::SecureOnly,
+            ('Q', _, None) => AddedChannelMode::NoKicks,
+
+            ('k', _, Some(key)) => AddedChannelMode::Key(key),
+            ('l', _, Some(limit)) => match limit.parse() {
+                Ok(limit) => AddedChannelMode::Limit(limit),
+                Err(_) => AddedChannelMode::Other('l', Some(limit)),
+            },
+            ('j', _, Some(throttle)) => AddedChannelMode::JoinThrottle(throttle),
+            ('f', _, Some(flood)) => AddedChannelMode::Flood(flood),
+            ('L', _, Some(channel)) => AddedChannelMode::Link(channel),
+
+            ('b', _, Some(mask)) => AddedChannelMode::Ban(mask),
+            ('e', _, Some(mask)) => AddedChannelMode::BanException(mask),
+            ('I', _, Some(mask)) => AddedChannelMode::InviteException(mask),
+
+            (mode, _, arg) => AddedChannelMode::Other(mode, arg),
         }
     }
 }
 
+/// A channel mode being unset, see `AddedChannelMode`.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub enum RemovedChannelMode {
     Op(String),
     Voice(String),
+    HalfOp(String),
+    Admin(String),
+    Owner(String),
 
+    Anonymous,
     InviteOnly,
     Moderated,
     NoExternal,
     Quiet(String),
     Private,
     Secret,
+    Reop,
     OpsTopic,
 
-    Key,
+    NoColors,
+    NoCtcp,
+    RegisteredOnly,
+    StripColors,
+    SecureOnly,
+    NoKicks,
+
+    /// The key is given back when the server lists `k` as always taking an argument.
+    Key(Option<String>),
     Limit,
+    /// Like the key, the settings of `j`, `f` and `L` are given back when the server lists their
+    /// mode as always taking an argument.
+    JoinThrottle(Option<String>),
+    Flood(Option<String>),
+    Link(Option<String>),
 
     Ban(String),
     BanException(String),
     InviteException(String),
+
+    /// A mode without a variant of its own, or one used in a way its variant cannot hold.
+    Other(char, Option<String>),
 }
 
 impl RemovedChannelMode {
-    fn from_valueless(
-        input: &[u8],
-        vlm: ValueLessChannelMode,
-    ) -> nom::IResult<&[u8], RemovedChannelMode> {
-        named!(mode_argument<&[u8], String>,
-           do_parse!(
-               tag!(" ") >>
-               arg: argument_maybe_last >>
-               (arg)
-           )
-        );
-
-        match vlm {
-            ValueLessChannelMode::Op => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, RemovedChannelMode::Op(arg.to_string())))
-            }
-            ValueLessChannelMode::Voice => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, RemovedChannelMode::Voice(arg.to_string())))
-            }
-
-            ValueLessChannelMode::InviteOnly => Ok((input, RemovedChannelMode::InviteOnly)),
-            ValueLessChannelMode::Moderated => Ok((input, RemovedChannelMode::Moderated)),
-            ValueLessChannelMode::NoExternal => Ok((input, RemovedChannelMode::NoExternal)),
-            ValueLessChannelMode::Quiet => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, RemovedChannelMode::Quiet(arg.to_string())))
+    pub(crate) fn new(
+        mode: char,
+        kind: ChannelModeKind,
+        arg: Option<String>,
+    ) -> RemovedChannelMode {
+        match (mode, kind, arg) {
+            ('o', _, Some(nick)) => RemovedChannelMode::Op(nick),
+            ('v', _, Some(nick)) => RemovedChannelMode::Voice(nick),
+            ('h', _, Some(nick)) => RemovedChannelMode::HalfOp(nick),
+            ('a', ChannelModeKind::Prefix, Some(nick)) => RemovedChannelMode::Admin(nick),
+            ('q', ChannelModeKind::Prefix, Some(nick)) => RemovedChannelMode::Owner(nick),
+
+            ('a', _, None) => RemovedChannelMode::Anonymous,
+            ('i', _, None) => RemovedChannelMode::InviteOnly,
+            ('m', _, None) => RemovedChannelMode::Moderated,
+            ('n', _, None) => RemovedChannelMode::NoExternal,
+            ('q', ChannelModeKind::List, Some(mask)) => RemovedChannelMode::Quiet(mask),
+            ('p', _, None) => RemovedChannelMode::Private,
+            ('s', _, None) => RemovedChannelMode::Secret,
+            ('r', _, None) => RemovedChannelMode::Reop,
+            ('t', _, None) => RemovedChannelMode::OpsTopic,
+
+            ('c', _, None) => RemovedChannelMode::NoColors,
+            ('C', _, None) => RemovedChannelMode::NoCtcp,
+            ('R', _, None) => RemovedChannelMode::RegisteredOnly,
+            ('S', _, None) => RemovedChannelMode::StripColors,
+            ('z', _, None) => RemovedChannelMode::SecureOnly,
+            ('Q', _, None) => RemovedChannelMode::NoKicks,
+
+            ('k', _, key) => RemovedChannelMode::Key(key),
+            ('l', _, None) => RemovedChannelMode::Limit,
+            ('j', _, Some(throttle)) => RemovedChannelMode::JoinThrottle(Some(throttle)),
+            ('j', ChannelModeKind::ParameterWhenSet, None) => {
+                RemovedChannelMode::JoinThrottle(None)
             }
-            ValueLessChannelMode::Private => Ok((input, RemovedChannelMode::Private)),
-            ValueLessChannelMode::Secret => Ok((input, RemovedChannelMode::Secret)),
-            ValueLessChannelMode::OpsTopic => Ok((input, RemovedChannelMode::OpsTopic)),
+            ('f', _, Some(flood)) => RemovedChannelMode::Flood(Some(flood)),
+            ('f', ChannelModeKind::ParameterWhenSet, None) => RemovedChannelMode::Flood(None),
+            ('L', _, Some(channel)) => RemovedChannelMode::Link(Some(channel)),
+            ('L', ChannelModeKind::ParameterWhenSet, None) => RemovedChannelMode::Link(None),
 
-            ValueLessChannelMode::Key => Ok((input, RemovedChannelMode::Key)),
-            ValueLessChannelMode::Limit => Ok((input, RemovedChannelMode::Limit)),
+            ('b', _, Some(mask)) => RemovedChannelMode::Ban(mask),
+            ('e', _, Some(mask)) => RemovedChannelMode::BanException(mask),
+            ('I', _, Some(mask)) => RemovedChannelMode::InviteException(mask),
 
-            ValueLessChannelMode::Ban => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, RemovedChannelMode::Ban(arg.to_string())))
-            }
-            ValueLessChannelMode::BanException => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((remaining, RemovedChannelMode::BanException(arg.to_string())))
-            }
-            ValueLessChannelMode::InviteException => {
-                let (remaining, arg) = mode_argument(input)?;
-                Ok((
-                    remaining,
-                    RemovedChannelMode::InviteException(arg.to_string()),
-                ))
-            }
+            (mode, _, arg) => RemovedChannelMode::Other(mode, arg),
         }
     }
 }
@@ -271,34 +440,205 @@ impl RemovedChannelMode {
 pub enum ChannelModeChange {
     Added(AddedChannelMode),
     Removed(RemovedChannelMode),
+    /// A list mode without a mask, such as `+b`, which asks the server for the entries of the
+    /// list.
+    ListQuery(char),
+}
+
+/// Turns the letters of a mode string into changes, taking each argument from `next_arg` as
+/// `table` says. Letters missing from the table are taken not to have any, and an added list mode
+/// left without an argument is a query for the list.
+///
+/// `next_arg` is told whether the argument may be left out, and gives `None` if it is.
+fn changes_from_letters<E, F>(
+    letters: Vec<(bool, char)>,
+    table: &ChannelModeTable,
+    mut next_arg: F,
+) -> Result<Vec<ChannelModeChange>, E>
+where
+    F: FnMut(char, ChannelModeKind, bool) -> Result<Option<String>, E>,
+{
+    let mut output = Vec::with_capacity(letters.len());
+    for (adding, mode) in letters {
+        let kind = table.kind(mode).unwrap_or(ChannelModeKind::Flag);
+        let arg = if kind.takes_argument(adding) {
+            match next_arg(mode, kind, adding && kind == ChannelModeKind::List)? {
+                Some(arg) => Some(arg),
+                None => {
+                    output.push(ChannelModeChange::ListQuery(mode));
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+        output.push(if adding {
+            ChannelModeChange::Added(AddedChannelMode::new(mode, kind, arg))
+        } else {
+            ChannelModeChange::Removed(RemovedChannelMode::new(mode, kind, arg))
+        });
+    }
+    Ok(output)
+}
+
+/// Parses a mode string and its arguments, taking arguments as `cfg.channel_modes` says.
+pub(crate) fn channel_modes<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+) -> nom::IResult<&'a [u8], Vec<ChannelModeChange>> {
+    let (mut remaining, letters) = mode_letters(input)?;
+    let output = changes_from_letters(letters, &cfg.channel_modes, |mode, kind, optional| {
+        match checked_argument(remaining, cfg, mode, kind) {
+            Ok((rest, arg)) => {
+                remaining = rest;
+                Ok(Some(arg))
+            }
+            Err(nom::Err::Error(_)) if optional => Ok(None),
+            Err(err) => Err(err),
+        }
+    })?;
+    Ok((remaining, output))
 }
 
-impl ChannelModeChange {
-    fn from_valueless(
-        input: &[u8],
-        mcp: ValueLessChannelModeChange,
-    ) -> nom::IResult<&[u8], ChannelModeChange> {
-        match mcp {
-            ValueLessChannelModeChange::Added(mp) => {
-                let (remaining, m) = AddedChannelMode::from_valueless(input, mp)?;
-                Ok((remaining, ChannelModeChange::Added(m)))
+/// Parses a channel mode string such as `+ntkl` together with its arguments, such as the
+/// parameters of RPL_CHANNELMODEIS (324) or a mode string kept in a configuration file.
+///
+/// Arguments are taken as `table` says, and checked as they are in a MODE command. Error offsets
+/// refer to the mode string and the arguments joined by single spaces; an error in an argument
+/// has the argument's index in `args` as its component, and one about a missing argument the
+/// index it would have had.
+pub fn parse_channel_modes<S>(
+    modes: &str,
+    args: &[S],
+    table: &ChannelModeTable,
+) -> Result<Vec<ChannelModeChange>, ParseError>
+where
+    S: AsRef<str>,
+{
+    let invalid = |rest: &[u8]| {
+        ParseError::new(
+            modes.len() - rest.len(),
+            Component::ModeString,
+            ErrorKind::InvalidModeString,
+        )
+    };
+    let letters = match mode_letters(modes.as_bytes()) {
+        Ok((&[], letters)) => letters,
+        Ok((rest, _)) => return Err(invalid(rest)),
+        Err(nom::Err::Failure(nom::Context::Code(rest, code)))
+            if code == ErrorKind::InvalidModeString.code() =>
+        {
+            return Err(invalid(rest))
+        }
+        Err(_) => {
+            return Err(ParseError::new(
+                0,
+                Component::ModeString,
+                ErrorKind::MissingModeSign,
+            ))
+        }
+    };
+
+    let mut offset = modes.len();
+    let mut used = 0;
+    let changes = changes_from_letters(letters, table, |mode, kind, optional| {
+        let arg = match args.get(used) {
+            Some(arg) => arg.as_ref(),
+            None if optional => return Ok(None),
+            None => {
+                return Err(ParseError::new(
+                    offset,
+                    Component::Parameter(used),
+                    ErrorKind::MissingParameter,
+                ))
             }
-            ValueLessChannelModeChange::Removed(mp) => {
-                let (remaining, m) = RemovedChannelMode::from_valueless(input, mp)?;
-                Ok((remaining, ChannelModeChange::Removed(m)))
+        };
+        let start = offset + 1;
+        match check_argument(mode, kind, arg) {
+            Ok(()) => {
+                offset = start + arg.len();
+                used += 1;
+                Ok(Some(arg.to_owned()))
             }
+            Err(reason) => Err(ParseError::new(start, Component::Parameter(used), reason)),
         }
+    })?;
+    if used < args.len() {
+        return Err(ParseError::new(
+            offset + 1,
+            Component::Parameter(used),
+            ErrorKind::UnexpectedParameter,
+        ));
     }
+    Ok(changes)
+}
+
+// User modes: https://tools.ietf.org/html/rfc2812#section-3.1.5
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum UserMode {
+    Invisible,
+    Wallops,
+    Oper,
+    LocalOper,
+    Registered,
+    /// Server notices, with the notice mask (snomask) if one was given.
+    ServerNotices(Option<String>),
+    /// A mode letter not defined by the RFC, such as a network's cloaking mode.
+    Other(char),
 }
 
-pub(crate) fn channel_modes(input: &[u8]) -> nom::IResult<&[u8], Vec<ChannelModeChange>> {
-    let mut output = Vec::new();
-    let (mut remaining, mcps) = ValueLessChannelModeChange::parse(input)?;
-    for mcp in mcps {
-        let res = ChannelModeChange::from_valueless(remaining, mcp);
-        let res = res?;
-        remaining = res.0;
-        output.push(res.1);
+impl UserMode {
+    fn from_char(c: char) -> UserMode {
+        match c {
+            'i' => UserMode::Invisible,
+            'w' => UserMode::Wallops,
+            'o' => UserMode::Oper,
+            'O' => UserMode::LocalOper,
+            'r' => UserMode::Registered,
+            's' => UserMode::ServerNotices(None),
+            _ => UserMode::Other(c),
+        }
     }
-    Ok((remaining, output))
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum UserModeChange {
+    Added(UserMode),
+    Removed(UserMode),
+}
+
+/// The changes of a MODE command, by whether its target is a channel or a nickname. A MODE
+/// without a mode string, which asks for the modes in effect, has none.
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub enum ModeChanges {
+    Channel(Option<Vec<ChannelModeChange>>),
+    User(Option<Vec<UserModeChange>>),
+}
+
+pub(crate) fn user_modes<'a>(
+    input: &'a [u8],
+    cfg: &ParserConfig,
+) -> nom::IResult<&'a [u8], Vec<UserModeChange>> {
+    let (mut remaining, letters) = mode_letters(input)?;
+    let mut changes = Vec::with_capacity(letters.len());
+    for (adding, mode) in letters {
+        let mut mode = UserMode::from_char(mode);
+        if !adding {
+            changes.push(UserModeChange::Removed(mode));
+            continue;
+        }
+        // Only an added `s` takes an argument, and even that one is optional. A parameter is only
+        // taken to be its snomask when it looks like one or nothing follows it.
+        if let UserMode::ServerNotices(ref mut mask) = mode {
+            if let Ok((rest, arg)) = mode_argument(remaining, cfg) {
+                if arg.starts_with(&['+', '-'][..]) || mode_argument(rest, cfg).is_err() {
+                    *mask = Some(arg);
+                    remaining = rest;
+                }
+            }
+        }
+        changes.push(UserModeChange::Added(mode));
+    }
+    Ok((remaining, changes))
 }
@@ -0,0 +1,211 @@
+// Numeric replies: https://tools.ietf.org/html/rfc2812#section-5
+
+macro_rules! replies {
+    ($($(#[$attr:meta])* $variant:ident = $code:expr, $name:expr;)*) => {
+        #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+        pub enum Reply {
+            $($(#[$attr])* $variant,)*
+            /// Any numeric not defined by RFC 1459 or RFC 2812.
+            Unknown(u16),
+        }
+
+        impl Reply {
+            pub fn from_code(code: u16) -> Reply {
+                match code {
+                    $($code => Reply::$variant,)*
+                    _ => Reply::Unknown(code),
+                }
+            }
+
+            pub fn code(&self) -> u16 {
+                match *self {
+                    $(Reply::$variant => $code,)*
+                    Reply::Unknown(code) => code,
+                }
+            }
+
+            /// The name the RFCs use for the reply, e.g. `RPL_WELCOME`.
+            pub fn name(&self) -> Option<&'static str> {
+                match *self {
+                    $(Reply::$variant => Some($name),)*
+                    Reply::Unknown(_) => None,
+                }
+            }
+        }
+    };
+}
+
+replies! {
+    RplWelcome = 1, "RPL_WELCOME";
+    RplYourHost = 2, "RPL_YOURHOST";
+    RplCreated = 3, "RPL_CREATED";
+    RplMyInfo = 4, "RPL_MYINFO";
+    /// Defined as RPL_BOUNCE by RFC 2812, but used for RPL_ISUPPORT by practically every server.
+    RplISupport = 5, "RPL_ISUPPORT";
+
+    RplTraceLink = 200, "RPL_TRACELINK";
+    RplTraceConnecting = 201, "RPL_TRACECONNECTING";
+    RplTraceHandshake = 202, "RPL_TRACEHANDSHAKE";
+    RplTraceUnknown = 203, "RPL_TRACEUNKNOWN";
+    RplTraceOperator = 204, "RPL_TRACEOPERATOR";
+    RplTraceUser = 205, "RPL_TRACEUSER";
+    RplTraceServer = 206, "RPL_TRACESERVER";
+    RplTraceService = 207, "RPL_TRACESERVICE";
+    RplTraceNewType = 208, "RPL_TRACENEWTYPE";
+    RplTraceClass = 209, "RPL_TRACECLASS";
+    RplTraceReconnect = 210, "RPL_TRACERECONNECT";
+    RplStatsLinkInfo = 211, "RPL_STATSLINKINFO";
+    RplStatsCommands = 212, "RPL_STATSCOMMANDS";
+    RplStatsCLine = 213, "RPL_STATSCLINE";
+    RplStatsNLine = 214, "RPL_STATSNLINE";
+    RplStatsILine = 215, "RPL_STATSILINE";
+    RplStatsKLine = 216, "RPL_STATSKLINE";
+    RplStatsQLine = 217, "RPL_STATSQLINE";
+    RplStatsYLine = 218, "RPL_STATSYLINE";
+    RplEndOfStats = 219, "RPL_ENDOFSTATS";
+    RplUModeIs = 221, "RPL_UMODEIS";
+    RplServiceInfo = 231, "RPL_SERVICEINFO";
+    RplEndOfServices = 232, "RPL_ENDOFSERVICES";
+    RplService = 233, "RPL_SERVICE";
+    RplServList = 234, "RPL_SERVLIST";
+    RplServListEnd = 235, "RPL_SERVLISTEND";
+    RplStatsIAuth = 239, "RPL_STATSIAUTH";
+    RplStatsVLine = 240, "RPL_STATSVLINE";
+    RplStatsLLine = 241, "RPL_STATSLLINE";
+    RplStatsUptime = 242, "RPL_STATSUPTIME";
+    RplStatsOLine = 243, "RPL_STATSOLINE";
+    RplStatsHLine = 244, "RPL_STATSHLINE";
+    RplStatsPing = 246, "RPL_STATSPING";
+    RplStatsBLine = 247, "RPL_STATSBLINE";
+    RplStatsDLine = 250, "RPL_STATSDLINE";
+    RplLUserClient = 251, "RPL_LUSERCLIENT";
+    RplLUserOp = 252, "RPL_LUSEROP";
+    RplLUserUnknown = 253, "RPL_LUSERUNKNOWN";
+    RplLUserChannels = 254, "RPL_LUSERCHANNELS";
+    RplLUserMe = 255, "RPL_LUSERME";
+    RplAdminMe = 256, "RPL_ADMINME";
+    RplAdminLoc1 = 257, "RPL_ADMINLOC1";
+    RplAdminLoc2 = 258, "RPL_ADMINLOC2";
+    RplAdminEmail = 259, "RPL_ADMINEMAIL";
+    RplTraceLog = 261, "RPL_TRACELOG";
+    RplTraceEnd = 262, "RPL_TRACEEND";
+    RplTryAgain = 263, "RPL_TRYAGAIN";
+
+    RplNone = 300, "RPL_NONE";
+    RplAway = 301, "RPL_AWAY";
+    RplUserHost = 302, "RPL_USERHOST";
+    RplIsOn = 303, "RPL_ISON";
+    RplUnAway = 305, "RPL_UNAWAY";
+    RplNowAway = 306, "RPL_NOWAWAY";
+    RplWhoisUser = 311, "RPL_WHOISUSER";
+    RplWhoisServer = 312, "RPL_WHOISSERVER";
+    RplWhoisOperator = 313, "RPL_WHOISOPERATOR";
+    RplWhowasUser = 314, "RPL_WHOWASUSER";
+    RplEndOfWho = 315, "RPL_ENDOFWHO";
+    RplWhoisChanOp = 316, "RPL_WHOISCHANOP";
+    RplWhoisIdle = 317, "RPL_WHOISIDLE";
+    RplEndOfWhois = 318, "RPL_ENDOFWHOIS";
+    RplWhoisChannels = 319, "RPL_WHOISCHANNELS";
+    RplListStart = 321, "RPL_LISTSTART";
+    RplList = 322, "RPL_LIST";
+    RplListEnd = 323, "RPL_LISTEND";
+    RplChannelModeIs = 324, "RPL_CHANNELMODEIS";
+    RplUniqOpIs = 325, "RPL_UNIQOPIS";
+    RplNoTopic = 331, "RPL_NOTOPIC";
+    RplTopic = 332, "RPL_TOPIC";
+    RplInviting = 341, "RPL_INVITING";
+    RplSummoning = 342, "RPL_SUMMONING";
+    RplInviteList = 346, "RPL_INVITELIST";
+    RplEndOfInviteList = 347, "RPL_ENDOFINVITELIST";
+    RplExceptList = 348, "RPL_EXCEPTLIST";
+    RplEndOfExceptList = 349, "RPL_ENDOFEXCEPTLIST";
+    RplVersion = 351, "RPL_VERSION";
+    RplWhoReply = 352, "RPL_WHOREPLY";
+    RplNamReply = 353, "RPL_NAMREPLY";
+    RplKillDone = 361, "RPL_KILLDONE";
+    RplClosing = 362, "RPL_CLOSING";
+    RplCloseEnd = 363, "RPL_CLOSEEND";
+    RplLinks = 364, "RPL_LINKS";
+    RplEndOfLinks = 365, "RPL_ENDOFLINKS";
+    RplEndOfNames = 366, "RPL_ENDOFNAMES";
+    RplBanList = 367, "RPL_BANLIST";
+    RplEndOfBanList = 368, "RPL_ENDOFBANLIST";
+    RplEndOfWhowas = 369, "RPL_ENDOFWHOWAS";
+    RplInfo = 371, "RPL_INFO";
+    RplMotd = 372, "RPL_MOTD";
+    RplInfoStart = 373, "RPL_INFOSTART";
+    RplEndOfInfo = 374, "RPL_ENDOFINFO";
+    RplMotdStart = 375, "RPL_MOTDSTART";
+    RplEndOfMotd = 376, "RPL_ENDOFMOTD";
+    RplYoureOper = 381, "RPL_YOUREOPER";
+    RplRehashing = 382, "RPL_REHASHING";
+    RplYoureService = 383, "RPL_YOURESERVICE";
+    RplMyPortIs = 384, "RPL_MYPORTIS";
+    RplTime = 391, "RPL_TIME";
+    RplUsersStart = 392, "RPL_USERSSTART";
+    RplUsers = 393, "RPL_USERS";
+    RplEndOfUsers = 394, "RPL_ENDOFUSERS";
+    RplNoUsers = 395, "RPL_NOUSERS";
+
+    ErrNoSuchNick = 401, "ERR_NOSUCHNICK";
+    ErrNoSuchServer = 402, "ERR_NOSUCHSERVER";
+    ErrNoSuchChannel = 403, "ERR_NOSUCHCHANNEL";
+    ErrCannotSendToChan = 404, "ERR_CANNOTSENDTOCHAN";
+    ErrTooManyChannels = 405, "ERR_TOOMANYCHANNELS";
+    ErrWasNoSuchNick = 406, "ERR_WASNOSUCHNICK";
+    ErrTooManyTargets = 407, "ERR_TOOMANYTARGETS";
+    ErrNoSuchService = 408, "ERR_NOSUCHSERVICE";
+    ErrNoOrigin = 409, "ERR_NOORIGIN";
+    ErrNoRecipient = 411, "ERR_NORECIPIENT";
+    ErrNoTextToSend = 412, "ERR_NOTEXTTOSEND";
+    ErrNoTopLevel = 413, "ERR_NOTOPLEVEL";
+    ErrWildTopLevel = 414, "ERR_WILDTOPLEVEL";
+    ErrBadMask = 415, "ERR_BADMASK";
+    ErrUnknownCommand = 421, "ERR_UNKNOWNCOMMAND";
+    ErrNoMotd = 422, "ERR_NOMOTD";
+    ErrNoAdminInfo = 423, "ERR_NOADMININFO";
+    ErrFileError = 424, "ERR_FILEERROR";
+    ErrNoNicknameGiven = 431, "ERR_NONICKNAMEGIVEN";
+    ErrErroneusNickname = 432, "ERR_ERRONEUSNICKNAME";
+    ErrNicknameInUse = 433, "ERR_NICKNAMEINUSE";
+    ErrNickCollision = 436, "ERR_NICKCOLLISION";
+    ErrUnavailResource = 437, "ERR_UNAVAILRESOURCE";
+    ErrUserNotInChannel = 441, "ERR_USERNOTINCHANNEL";
+    ErrNotOnChannel = 442, "ERR_NOTONCHANNEL";
+    ErrUserOnChannel = 443, "ERR_USERONCHANNEL";
+    ErrNoLogin = 444, "ERR_NOLOGIN";
+    ErrSummonDisabled = 445, "ERR_SUMMONDISABLED";
+    ErrUsersDisabled = 446, "ERR_USERSDISABLED";
+    ErrNotRegistered = 451, "ERR_NOTREGISTERED";
+    ErrNeedMoreParams = 461, "ERR_NEEDMOREPARAMS";
+    ErrAlreadyRegistred = 462, "ERR_ALREADYREGISTRED";
+    ErrNoPermForHost = 463, "ERR_NOPERMFORHOST";
+    ErrPasswdMismatch = 464, "ERR_PASSWDMISMATCH";
+    ErrYoureBannedCreep = 465, "ERR_YOUREBANNEDCREEP";
+    ErrYouWillBeBanned = 466, "ERR_YOUWILLBEBANNED";
+    ErrKeySet = 467, "ERR_KEYSET";
+    ErrChannelIsFull = 471, "ERR_CHANNELISFULL";
+    ErrUnknownMode = 472, "ERR_UNKNOWNMODE";
+    ErrInviteOnlyChan = 473, "ERR_INVITEONLYCHAN";
+    ErrBannedFromChan = 474, "ERR_BANNEDFROMCHAN";
+    ErrBadChannelKey = 475, "ERR_BADCHANNELKEY";
+    ErrBadChanMask = 476, "ERR_BADCHANMASK";
+    ErrNoChanModes = 477, "ERR_NOCHANMODES";
+    ErrBanListFull = 478, "ERR_BANLISTFULL";
+    ErrNoPrivileges = 481, "ERR_NOPRIVILEGES";
+    ErrChanOPrivsNeeded = 482, "ERR_CHANOPRIVSNEEDED";
+    ErrCantKillServer = 483, "ERR_CANTKILLSERVER";
+    ErrRestricted = 484, "ERR_RESTRICTED";
+    ErrUniqOpPrivsNeeded = 485, "ERR_UNIQOPPRIVSNEEDED";
+    ErrNoOperHost = 491, "ERR_NOOPERHOST";
+    ErrNoServiceHost = 492, "ERR_NOSERVICEHOST";
+
+    ErrUModeUnknownFlag = 501, "ERR_UMODEUNKNOWNFLAG";
+    ErrUsersDontMatch = 502, "ERR_USERSDONTMATCH";
+}
+
+impl From<u16> for Reply {
+    fn from(code: u16) -> Reply {
+        Reply::from_code(code)
+    }
+}
@@ -1,109 +1,372 @@
 use std::fmt::{self, Write};
+use std::mem;
 
-use {AddedChannelMode, ChannelModeChange, Command, Message, Prefix, RemovedChannelMode};
+use tags::escape_value;
+use {
+    AddedChannelMode, ChannelModeChange, Command, Limits, Message, ModeChanges, Prefix,
+    RemovedChannelMode, Reply, SerializeError, Tags, Text, UserMode, UserModeChange,
+};
+
+/// Somewhere to serialize to byte for byte, see `Serialize::serialize_bytes`.
+///
+/// Parameters are written with `write_bytes`, so that a byte-oriented output receives them exactly
+/// as they were parsed. Text-only outputs decode them lossily.
+pub trait Output: Write {
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result;
+}
+
+impl Output for String {
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.write_str(&String::from_utf8_lossy(bytes))
+    }
+}
+
+impl<'a> Output for fmt::Formatter<'a> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.write_str(&String::from_utf8_lossy(bytes))
+    }
+}
+
+/// Adapts any `fmt::Write` to an `Output` that decodes parameters lossily.
+struct Lossy<'a, T: 'a>(&'a mut T);
+
+impl<'a, T> Write for Lossy<'a, T>
+where
+    T: Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+}
+
+impl<'a, T> Output for Lossy<'a, T>
+where
+    T: Write,
+{
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.0.write_str(&String::from_utf8_lossy(bytes))
+    }
+}
+
+struct Bytes(Vec<u8>);
+
+impl Write for Bytes {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Output for Bytes {
+    fn write_bytes(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
 
 pub trait Serialize {
+    /// Serializes as text, decoding parameters that are not valid UTF-8 lossily. Use
+    /// `serialize_bytes` or `to_bytes` to write them back exactly as they were received.
     fn serialize<T>(&self, buf: &mut T) -> fmt::Result
     where
-        T: Write;
+        T: Write,
+    {
+        self.serialize_bytes(&mut Lossy(buf))
+    }
+
+    /// Serializes to `buf`, writing parameters with `Output::write_bytes`.
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output;
+
+    /// Serializes to bytes, writing parameters back exactly as they were received.
+    fn to_bytes(&self) -> Result<Vec<u8>, fmt::Error> {
+        let mut buf = Bytes(Vec::new());
+        self.serialize_bytes(&mut buf)?;
+        Ok(buf.0)
+    }
 }
 
-impl Serialize for Prefix {
-    fn serialize<T>(&self, buf: &mut T) -> fmt::Result
+impl<'a> Serialize for Text<'a> {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
     where
-        T: Write,
+        T: Output,
+    {
+        buf.write_bytes(self.as_bytes())
+    }
+}
+
+/// Writes `items` separated by `separator`.
+fn write_list<T>(buf: &mut T, items: &[Text], separator: &str) -> fmt::Result
+where
+    T: Output,
+{
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            buf.write_str(separator)?;
+        }
+        item.serialize_bytes(buf)?;
+    }
+    Ok(())
+}
+
+impl<'a> Serialize for Prefix<'a> {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
     {
-        write!(buf, ":{}", self.0)?;
+        match *self {
+            Prefix::Server(ref name) => write!(buf, ":{}", name)?,
+            Prefix::User {
+                ref nick,
+                ref user,
+                ref host,
+            } => {
+                write!(buf, ":{}", nick)?;
+                if let Some(user) = user {
+                    write!(buf, "!{}", user)?;
+                }
+                if let Some(host) = host {
+                    write!(buf, "@{}", host)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
-impl Serialize for Message {
-    fn serialize<T>(&self, buf: &mut T) -> fmt::Result
+/// Writes space-separated parameters, making the last one a trailing parameter when it has to be
+/// or when `trailing` asks for it.
+fn write_params<T>(buf: &mut T, params: &[Text], trailing: bool) -> fmt::Result
+where
+    T: Output,
+{
+    if let Some((last, middle)) = params.split_last() {
+        for param in middle {
+            buf.write_str(" ")?;
+            param.serialize_bytes(buf)?;
+        }
+        if trailing || last.is_empty() || last.contains(' ') || last.starts_with(':') {
+            buf.write_str(" :")?;
+        } else {
+            buf.write_str(" ")?;
+        }
+        last.serialize_bytes(buf)?;
+    }
+    Ok(())
+}
+
+impl Serialize for Reply {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
+    {
+        write!(buf, "{:03}", self.code())?;
+        Ok(())
+    }
+}
+
+impl<'a> Serialize for Tags<'a> {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
+    {
+        for (idx, tag) in self.iter().enumerate() {
+            buf.write_char(if idx == 0 { '@' } else { ';' })?;
+            write!(buf, "{}", tag.key)?;
+            if let Some(ref value) = tag.value {
+                write!(buf, "={}", escape_value(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Message<'a> {
+    /// Serializes the message, failing without writing anything if it exceeds `limits`.
+    ///
+    /// Parameters are decoded lossily as with `serialize`; `to_bytes_with` keeps them exact.
+    pub fn serialize_with<T>(&self, buf: &mut T, limits: &Limits) -> Result<(), SerializeError>
     where
         T: Write,
     {
+        let line = self.to_bytes_with(limits)?;
+        buf.write_str(&String::from_utf8_lossy(&line))?;
+        Ok(())
+    }
+
+    /// Serializes the message to bytes, failing if it exceeds `limits`.
+    pub fn to_bytes_with(&self, limits: &Limits) -> Result<Vec<u8>, SerializeError> {
+        let line = self.to_bytes()?;
+        limits
+            .check(&line)
+            .map_err(|(_, kind)| SerializeError::from_kind(kind))?;
+        Ok(line)
+    }
+}
+
+impl<'a> Serialize for Message<'a> {
+    /// Writes the message whatever its size. Use `serialize_with` or `to_bytes_with` to hold it to
+    /// the `Limits` of the server.
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
+    {
+        if !self.tags.is_empty() {
+            self.tags.serialize_bytes(buf)?;
+            write!(buf, " ")?
+        }
         if let Some(ref p) = self.prefix {
-            p.serialize(buf)?;
+            p.serialize_bytes(buf)?;
             write!(buf, " ")?
         }
-        self.command.serialize(buf)?;
+        self.command.serialize_bytes(buf)?;
         write!(buf, "\r\n")?;
         Ok(())
     }
 }
 
+/// Builds a mode string with its arguments, keeping the changes in order and each argument in the
+/// position of its letter. A sign is only written where it differs from the one before.
+fn mode_string<I, S>(changes: I) -> (String, Vec<S>)
+where
+    I: IntoIterator<Item = (char, char, Option<S>)>,
+{
+    let mut action = None;
+    let mut modes = String::new();
+    let mut values = Vec::new();
+    for (sign, modechar, value) in changes {
+        if action != Some(sign) {
+            modes.push(sign);
+            action = Some(sign);
+        }
+        modes.push(modechar);
+        values.extend(value);
+    }
+    (modes, values)
+}
+
+/// Writes a space and then the mode string and arguments of `changes`, if there are any.
+fn write_modes<T, I, S>(buf: &mut T, changes: I) -> fmt::Result
+where
+    T: Output,
+    I: IntoIterator<Item = (char, char, Option<S>)>,
+    S: AsRef<str>,
+{
+    let (modes, values) = mode_string(changes);
+    if !modes.is_empty() {
+        write!(buf, " {}", modes)?;
+    }
+    for value in values {
+        write!(buf, " {}", value.as_ref())?;
+    }
+    Ok(())
+}
+
+/// Formats channel mode changes as a mode string and its arguments, the reverse of
+/// `parse_channel_modes`.
+pub fn format_channel_modes(changes: &[ChannelModeChange]) -> (String, Vec<String>) {
+    mode_string(changes.iter().map(ChannelModeChange::to_tuple))
+}
+
 impl Serialize for Vec<ChannelModeChange> {
-    fn serialize<T>(&self, buf: &mut T) -> fmt::Result
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
     where
-        T: Write,
+        T: Output,
     {
-        let (added, removed): (Vec<_>, Vec<_>) = self
-            .iter()
-            .map(ChannelModeChange::to_tuple)
-            .partition(|(action, _, _)| *action == '+');
-
-        let mut added: (String, Vec<String>) = added.iter().fold(
-            (String::new(), Vec::new()),
-            |(mut modechars, mut values), (_, modechar, value)| {
-                modechars.push(*modechar);
-                if let Some(v) = value {
-                    values.push(v.to_owned());
-                }
-                (modechars, values)
-            },
-        );
-
-        let mut removed: (String, Vec<String>) = removed.iter().fold(
-            (String::new(), Vec::new()),
-            |(mut modechars, mut values), (_, modechar, value)| {
-                modechars.push(*modechar);
-                if let Some(v) = value {
-                    values.push(v.to_owned());
-                }
-                (modechars, values)
-            },
-        );
-
-        let mut out = String::new();
-        let mut values: Vec<String> = Vec::new();
-
-        if !added.0.is_empty() {
-            write!(out, "+{}", added.0)?;
-            values.append(&mut added.1);
-        };
-        if !removed.0.is_empty() {
-            write!(out, "-{}", removed.0)?;
-            values.append(&mut removed.1);
-        };
+        write_modes(buf, self.iter().map(ChannelModeChange::to_tuple))
+    }
+}
 
-        if !values.is_empty() {
-            write!(out, " {}", values.join(" "))?;
-        };
+impl Serialize for Vec<UserModeChange> {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
+    {
+        write_modes(buf, self.iter().map(UserModeChange::to_tuple))
+    }
+}
 
-        if !out.is_empty() {
-            write!(buf, " {}", out)?;
-        };
+impl Serialize for ModeChanges {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
+    where
+        T: Output,
+    {
+        match *self {
+            ModeChanges::Channel(Some(ref changes)) => changes.serialize_bytes(buf),
+            ModeChanges::User(Some(ref changes)) => changes.serialize_bytes(buf),
+            ModeChanges::Channel(None) | ModeChanges::User(None) => Ok(()),
+        }
+    }
+}
 
-        Ok(())
+impl<'a> Command<'a> {
+    /// Splits `changes` into as few MODE commands on `channel` as the server accepts, keeping
+    /// their order.
+    ///
+    /// Each command has at most `max_modes` changes with an argument, see
+    /// `ChannelModeTable::max_modes`, and fits within `limits` when sent without a prefix. A change
+    /// too long to fit a line of its own is still given one.
+    pub fn split_modes(
+        channel: Text<'a>,
+        changes: Vec<ChannelModeChange>,
+        max_modes: usize,
+        limits: &Limits,
+    ) -> Vec<Command<'a>> {
+        // "MODE <channel> " plus the line terminator.
+        let base = 5 + channel.as_bytes().len() + 3;
+        let mut commands = Vec::new();
+        let mut batch = Vec::new();
+        let (mut len, mut args, mut action) = (base, 0, None);
+        for change in changes {
+            let (sign, modechar, value) = change.to_tuple();
+            let arg_len = value.map_or(0, |value| 1 + value.len());
+            let has_arg = arg_len > 0;
+            let cost =
+                |action| modechar.len_utf8() + arg_len + if action == Some(sign) { 0 } else { 1 };
+            let full = len + cost(action) > limits.body
+                || has_arg && (args >= max_modes || 2 + args >= limits.params);
+            if full && !batch.is_empty() {
+                commands.push(Command::Mode {
+                    target: channel.clone(),
+                    modechanges: ModeChanges::Channel(Some(mem::take(&mut batch))),
+                });
+                len = base;
+                args = 0;
+                action = None;
+            }
+            len += cost(action);
+            args += has_arg as usize;
+            action = Some(sign);
+            batch.push(change);
+        }
+        if !batch.is_empty() {
+            commands.push(Command::Mode {
+                target: channel,
+                modechanges: ModeChanges::Channel(Some(batch)),
+            });
+        }
+        commands
     }
 }
 
-impl Serialize for Command {
-    fn serialize<T>(&self, buf: &mut T) -> fmt::Result
+impl<'a> Serialize for Command<'a> {
+    fn serialize_bytes<T>(&self, buf: &mut T) -> fmt::Result
     where
-        T: Write,
+        T: Output,
     {
         match *self {
             Command::Pass { ref password } => {
-                write!(buf, "PASS :{}", password)?;
+                buf.write_str("PASS :")?;
+                password.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Nick {
                 ref nickname,
                 ref hopcount,
             } => {
-                write!(buf, "NICK {}", nickname)?;
+                buf.write_str("NICK ")?;
+                nickname.serialize_bytes(buf)?;
                 if let Some(hc) = hopcount {
                     write!(buf, " {}", hc)?;
                 };
@@ -115,11 +378,14 @@ impl Serialize for Command {
                 ref servername,
                 ref realname,
             } => {
-                write!(
-                    buf,
-                    "USER {} {} {} :{}",
-                    username, hostname, servername, realname
-                )?;
+                buf.write_str("USER ")?;
+                username.serialize_bytes(buf)?;
+                buf.write_str(" ")?;
+                hostname.serialize_bytes(buf)?;
+                buf.write_str(" ")?;
+                servername.serialize_bytes(buf)?;
+                buf.write_str(" :")?;
+                realname.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Server {
@@ -127,20 +393,27 @@ impl Serialize for Command {
                 ref hopcount,
                 ref info,
             } => {
-                write!(buf, "SERVER {} {} :{}", servername, hopcount, info)?;
+                buf.write_str("SERVER ")?;
+                servername.serialize_bytes(buf)?;
+                write!(buf, " {} :", hopcount)?;
+                info.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Oper {
                 ref user,
                 ref password,
             } => {
-                write!(buf, "OPER {} :{}", user, password)?;
+                buf.write_str("OPER ")?;
+                user.serialize_bytes(buf)?;
+                buf.write_str(" :")?;
+                password.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Quit { ref message } => {
                 write!(buf, "QUIT")?;
                 if let Some(m) = message {
-                    write!(buf, " :{}", m)?;
+                    buf.write_str(" :")?;
+                    m.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -148,44 +421,62 @@ impl Serialize for Command {
                 ref server,
                 ref comment,
             } => {
-                write!(buf, "SQUIT {} :{}", server, comment)?;
+                buf.write_str("SQUIT ")?;
+                server.serialize_bytes(buf)?;
+                buf.write_str(" :")?;
+                comment.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Join {
                 ref channels,
                 ref keys,
             } => {
-                let channels = channels.join(",");
-                let keys = keys.join(",");
-                write!(buf, "JOIN {} :{}", channels, keys)?;
+                buf.write_str("JOIN ")?;
+                write_list(buf, channels, ",")?;
+                buf.write_str(" :")?;
+                write_list(buf, keys, ",")?;
+                Ok(())
+            }
+            Command::Part {
+                ref channels,
+                ref message,
+            } => {
+                buf.write_str("PART ")?;
+                write_list(buf, channels, ",")?;
+                if let Some(m) = message {
+                    buf.write_str(" :")?;
+                    m.serialize_bytes(buf)?;
+                };
                 Ok(())
             }
-            Command::Part { ref channels } => {
-                write!(buf, "PART {}", channels.join(","))?;
+            Command::PartAll => {
+                write!(buf, "JOIN 0")?;
                 Ok(())
             }
             Command::Mode {
                 ref target,
                 ref modechanges,
             } => {
-                write!(buf, "MODE {}", target)?;
-                if let Some(mcs) = modechanges {
-                    mcs.serialize(buf)?;
-                };
+                buf.write_str("MODE ")?;
+                target.serialize_bytes(buf)?;
+                modechanges.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Topic {
                 ref channel,
                 ref topic,
             } => {
-                write!(buf, "TOPIC {}", channel)?;
+                buf.write_str("TOPIC ")?;
+                channel.serialize_bytes(buf)?;
                 if let Some(t) = topic {
-                    write!(buf, " :{}", t)?;
+                    buf.write_str(" :")?;
+                    t.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
             Command::Names { ref channels } => {
-                write!(buf, "NAMES {}", channels.join(","))?;
+                buf.write_str("NAMES ")?;
+                write_list(buf, channels, ",")?;
                 Ok(())
             }
             Command::List {
@@ -193,12 +484,13 @@ impl Serialize for Command {
                 ref server,
             } => {
                 write!(buf, "LIST")?;
-                let channels = channels.join(",");
                 if !channels.is_empty() {
-                    write!(buf, " {}", channels)?;
+                    buf.write_str(" ")?;
+                    write_list(buf, channels, ",")?;
                 };
                 if let Some(s) = server {
-                    write!(buf, " :{}", s)?;
+                    buf.write_str(" :")?;
+                    s.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -206,24 +498,32 @@ impl Serialize for Command {
                 ref nickname,
                 ref channel,
             } => {
-                write!(buf, "INVITE {} {}", nickname, channel)?;
+                buf.write_str("INVITE ")?;
+                nickname.serialize_bytes(buf)?;
+                buf.write_str(" ")?;
+                channel.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Kick {
-                ref channel,
-                ref user,
+                ref channels,
+                ref users,
                 ref comment,
             } => {
-                write!(buf, "KICK {} {}", channel, user)?;
+                buf.write_str("KICK ")?;
+                write_list(buf, channels, ",")?;
+                buf.write_str(" ")?;
+                write_list(buf, users, ",")?;
                 if let Some(c) = comment {
-                    write!(buf, " :{}", c)?;
+                    buf.write_str(" :")?;
+                    c.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
             Command::Version { ref server } => {
                 write!(buf, "VERSION")?;
                 if let Some(s) = server {
-                    write!(buf, " {}", s)?;
+                    buf.write_str(" ")?;
+                    s.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -233,10 +533,12 @@ impl Serialize for Command {
             } => {
                 write!(buf, "STATS")?;
                 if let Some(q) = query {
-                    write!(buf, " {}", q)?;
+                    buf.write_str(" ")?;
+                    q.serialize_bytes(buf)?;
                 };
                 if let Some(s) = server {
-                    write!(buf, " {}", s)?;
+                    buf.write_str(" ")?;
+                    s.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -246,17 +548,20 @@ impl Serialize for Command {
             } => {
                 write!(buf, "LINKS")?;
                 if let Some(rs) = remote_server {
-                    write!(buf, " {}", rs)?;
+                    buf.write_str(" ")?;
+                    rs.serialize_bytes(buf)?;
                 };
                 if let Some(sm) = server_mask {
-                    write!(buf, " {}", sm)?;
+                    buf.write_str(" ")?;
+                    sm.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
             Command::Time { ref server } => {
                 write!(buf, "TIME")?;
                 if let Some(s) = server {
-                    write!(buf, " {}", s)?;
+                    buf.write_str(" ")?;
+                    s.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
@@ -265,33 +570,39 @@ impl Serialize for Command {
                 ref port,
                 ref remote_server,
             } => {
-                write!(buf, "CONNECT {}", target_server)?;
+                buf.write_str("CONNECT ")?;
+                target_server.serialize_bytes(buf)?;
                 if let Some(port) = port {
-                    write!(buf, " {}", port)?;
+                    buf.write_str(" ")?;
+                    port.serialize_bytes(buf)?;
                 }
                 if let Some(rs) = remote_server {
-                    write!(buf, " {}", rs)?;
+                    buf.write_str(" ")?;
+                    rs.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
             Command::Trace { ref server } => {
                 write!(buf, "TRACE")?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
             Command::Admin { ref server } => {
                 write!(buf, "ADMIN")?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
             Command::Info { ref server } => {
                 write!(buf, "INFO")?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -299,23 +610,31 @@ impl Serialize for Command {
                 ref receivers,
                 ref message,
             } => {
-                write!(buf, "PRIVMSG {} :{}", receivers.join(","), message)?;
+                buf.write_str("PRIVMSG ")?;
+                write_list(buf, receivers, ",")?;
+                buf.write_str(" :")?;
+                message.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Notice {
-                ref nickname,
+                ref receivers,
                 ref text,
             } => {
-                write!(buf, "NOTICE {} :{}", nickname, text)?;
+                buf.write_str("NOTICE ")?;
+                write_list(buf, receivers, ",")?;
+                buf.write_str(" :")?;
+                text.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Who { ref name, ref o } => {
                 write!(buf, "WHO")?;
                 if let Some(name) = name {
-                    write!(buf, " {}", name)?;
+                    buf.write_str(" ")?;
+                    name.serialize_bytes(buf)?;
                 }
                 if let Some(o) = o {
-                    write!(buf, " {}", o)?;
+                    buf.write_str(" ")?;
+                    o.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
@@ -325,9 +644,11 @@ impl Serialize for Command {
             } => {
                 write!(buf, "WHOIS")?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 }
-                write!(buf, " {}", nickmasks.join(","))?;
+                buf.write_str(" ")?;
+                write_list(buf, nickmasks, ",")?;
                 Ok(())
             }
             Command::Whowas {
@@ -335,12 +656,15 @@ impl Serialize for Command {
                 ref count,
                 ref server,
             } => {
-                write!(buf, "WHOWAS {}", nickname)?;
+                buf.write_str("WHOWAS ")?;
+                nickname.serialize_bytes(buf)?;
                 if let Some(count) = count {
-                    write!(buf, " {}", count)?;
+                    buf.write_str(" ")?;
+                    count.serialize_bytes(buf)?;
                 };
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -348,16 +672,21 @@ impl Serialize for Command {
                 ref nickname,
                 ref comment,
             } => {
-                write!(buf, "KILL {} :{}", nickname, comment)?;
+                buf.write_str("KILL ")?;
+                nickname.serialize_bytes(buf)?;
+                buf.write_str(" :")?;
+                comment.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Ping {
                 ref server1,
                 ref server2,
             } => {
-                write!(buf, "PING {}", server1)?;
+                buf.write_str("PING ")?;
+                server1.serialize_bytes(buf)?;
                 if let Some(server2) = server2 {
-                    write!(buf, " {}", server2)?;
+                    buf.write_str(" ")?;
+                    server2.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
@@ -365,20 +694,24 @@ impl Serialize for Command {
                 ref daemon1,
                 ref daemon2,
             } => {
-                write!(buf, "PONG {}", daemon1)?;
+                buf.write_str("PONG ")?;
+                daemon1.serialize_bytes(buf)?;
                 if let Some(daemon2) = daemon2 {
-                    write!(buf, " {}", daemon2)?;
+                    buf.write_str(" ")?;
+                    daemon2.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
             Command::Error { ref message } => {
-                write!(buf, "ERROR :{}", message)?;
+                buf.write_str("ERROR :")?;
+                message.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Away { ref message } => {
                 write!(buf, "AWAY")?;
                 if let Some(message) = message {
-                    write!(buf, " :{}", message)?;
+                    buf.write_str(" :")?;
+                    message.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
@@ -394,34 +727,128 @@ impl Serialize for Command {
                 ref user,
                 ref server,
             } => {
-                write!(buf, "SUMMON {}", user)?;
+                buf.write_str("SUMMON ")?;
+                user.serialize_bytes(buf)?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 };
                 Ok(())
             }
             Command::Users { ref server } => {
                 write!(buf, "USERS")?;
                 if let Some(server) = server {
-                    write!(buf, " {}", server)?;
+                    buf.write_str(" ")?;
+                    server.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
             Command::Wallops { ref text } => {
-                write!(buf, "WALLOPS :{}", text)?;
+                buf.write_str("WALLOPS :")?;
+                text.serialize_bytes(buf)?;
                 Ok(())
             }
             Command::Userhost { ref nicknames } => {
-                write!(buf, "USERHOST {}", nicknames.join(" "))?;
+                buf.write_str("USERHOST ")?;
+                write_list(buf, nicknames, " ")?;
                 Ok(())
             }
             Command::Ison { ref nicknames } => {
                 write!(buf, "ISON")?;
                 for nick in nicknames {
-                    write!(buf, " {}", nick)?;
+                    buf.write_str(" ")?;
+                    nick.serialize_bytes(buf)?;
                 }
                 Ok(())
             }
+            Command::Motd { ref target } => {
+                write!(buf, "MOTD")?;
+                if let Some(target) = target {
+                    buf.write_str(" ")?;
+                    target.serialize_bytes(buf)?;
+                }
+                Ok(())
+            }
+            Command::Lusers {
+                ref mask,
+                ref target,
+            } => {
+                write!(buf, "LUSERS")?;
+                if let Some(m) = mask {
+                    buf.write_str(" ")?;
+                    m.serialize_bytes(buf)?;
+                };
+                if let Some(t) = target {
+                    buf.write_str(" ")?;
+                    t.serialize_bytes(buf)?;
+                };
+                Ok(())
+            }
+            Command::Service {
+                ref nickname,
+                ref distribution,
+                ref service_type,
+                ref info,
+            } => {
+                buf.write_str("SERVICE ")?;
+                nickname.serialize_bytes(buf)?;
+                buf.write_str(" * ")?;
+                distribution.serialize_bytes(buf)?;
+                buf.write_str(" ")?;
+                service_type.serialize_bytes(buf)?;
+                buf.write_str(" 0 :")?;
+                info.serialize_bytes(buf)?;
+                Ok(())
+            }
+            Command::Squery {
+                ref servicename,
+                ref text,
+            } => {
+                buf.write_str("SQUERY ")?;
+                servicename.serialize_bytes(buf)?;
+                buf.write_str(" :")?;
+                text.serialize_bytes(buf)?;
+                Ok(())
+            }
+            Command::Servlist {
+                ref mask,
+                ref service_type,
+            } => {
+                write!(buf, "SERVLIST")?;
+                if let Some(m) = mask {
+                    buf.write_str(" ")?;
+                    m.serialize_bytes(buf)?;
+                };
+                if let Some(t) = service_type {
+                    buf.write_str(" ")?;
+                    t.serialize_bytes(buf)?;
+                };
+                Ok(())
+            }
+            Command::Die => {
+                write!(buf, "DIE")?;
+                Ok(())
+            }
+            Command::Numeric {
+                ref code,
+                ref params,
+                trailing,
+            } => {
+                code.serialize_bytes(buf)?;
+                write_params(buf, params, trailing)?;
+                Ok(())
+            }
+            Command::Raw {
+                ref verb,
+                ref params,
+                trailing,
+            } => {
+                for c in verb.chars() {
+                    buf.write_char(c.to_ascii_uppercase())?;
+                }
+                write_params(buf, params, trailing)?;
+                Ok(())
+            }
         }
     }
 }
@@ -431,18 +858,33 @@ impl AddedChannelMode {
         match *self {
             AddedChannelMode::Op(ref target) => ('o', Some(target.to_owned())),
             AddedChannelMode::Voice(ref target) => ('v', Some(target.to_owned())),
+            AddedChannelMode::HalfOp(ref target) => ('h', Some(target.to_owned())),
+            AddedChannelMode::Admin(ref target) => ('a', Some(target.to_owned())),
+            AddedChannelMode::Owner(ref target) => ('q', Some(target.to_owned())),
+            AddedChannelMode::Anonymous => ('a', None),
             AddedChannelMode::InviteOnly => ('i', None),
             AddedChannelMode::Moderated => ('m', None),
             AddedChannelMode::NoExternal => ('n', None),
             AddedChannelMode::Quiet(ref target) => ('q', Some(target.to_owned())),
             AddedChannelMode::Private => ('p', None),
             AddedChannelMode::Secret => ('s', None),
+            AddedChannelMode::Reop => ('r', None),
             AddedChannelMode::OpsTopic => ('t', None),
+            AddedChannelMode::NoColors => ('c', None),
+            AddedChannelMode::NoCtcp => ('C', None),
+            AddedChannelMode::RegisteredOnly => ('R', None),
+            AddedChannelMode::StripColors => ('S', None),
+            AddedChannelMode::SecureOnly => ('z', None),
+            AddedChannelMode::NoKicks => ('Q', None),
             AddedChannelMode::Key(ref key) => ('k', Some(key.to_owned())),
             AddedChannelMode::Limit(ref limit) => ('l', Some(limit.to_string())),
+            AddedChannelMode::JoinThrottle(ref throttle) => ('j', Some(throttle.to_owned())),
+            AddedChannelMode::Flood(ref flood) => ('f', Some(flood.to_owned())),
+            AddedChannelMode::Link(ref channel) => ('L', Some(channel.to_owned())),
             AddedChannelMode::Ban(ref target) => ('b', Some(target.to_owned())),
             AddedChannelMode::BanException(ref target) => ('e', Some(target.to_owned())),
             AddedChannelMode::InviteException(ref target) => ('I', Some(target.to_owned())),
+            AddedChannelMode::Other(mode, ref value) => (mode, value.clone()),
         }
     }
 }
@@ -452,24 +894,40 @@ impl RemovedChannelMode {
         match *self {
             RemovedChannelMode::Op(ref target) => ('o', Some(target.to_owned())),
             RemovedChannelMode::Voice(ref target) => ('v', Some(target.to_owned())),
+            RemovedChannelMode::HalfOp(ref target) => ('h', Some(target.to_owned())),
+            RemovedChannelMode::Admin(ref target) => ('a', Some(target.to_owned())),
+            RemovedChannelMode::Owner(ref target) => ('q', Some(target.to_owned())),
+            RemovedChannelMode::Anonymous => ('a', None),
             RemovedChannelMode::InviteOnly => ('i', None),
             RemovedChannelMode::Moderated => ('m', None),
             RemovedChannelMode::NoExternal => ('n', None),
             RemovedChannelMode::Quiet(ref target) => ('q', Some(target.to_owned())),
             RemovedChannelMode::Private => ('p', None),
             RemovedChannelMode::Secret => ('s', None),
+            RemovedChannelMode::Reop => ('r', None),
             RemovedChannelMode::OpsTopic => ('t', None),
-            RemovedChannelMode::Key => ('k', None),
+            RemovedChannelMode::NoColors => ('c', None),
+            RemovedChannelMode::NoCtcp => ('C', None),
+            RemovedChannelMode::RegisteredOnly => ('R', None),
+            RemovedChannelMode::StripColors => ('S', None),
+            RemovedChannelMode::SecureOnly => ('z', None),
+            RemovedChannelMode::NoKicks => ('Q', None),
+            RemovedChannelMode::Key(ref key) => ('k', key.clone()),
             RemovedChannelMode::Limit => ('l', None),
+            RemovedChannelMode::JoinThrottle(ref throttle) => ('j', throttle.clone()),
+            RemovedChannelMode::Flood(ref flood) => ('f', flood.clone()),
+            RemovedChannelMode::Link(ref channel) => ('L', channel.clone()),
             RemovedChannelMode::Ban(ref target) => ('b', Some(target.to_owned())),
             RemovedChannelMode::BanException(ref target) => ('e', Some(target.to_owned())),
             RemovedChannelMode::InviteException(ref target) => ('I', Some(target.to_owned())),
+            RemovedChannelMode::Other(mode, ref value) => (mode, value.clone()),
         }
     }
 }
 
 impl ChannelModeChange {
-    pub(crate) fn to_tuple(&self) -> (char, char, Option<String>) {
+    /// The sign, mode letter and argument of the change.
+    pub fn to_tuple(&self) -> (char, char, Option<String>) {
         match *self {
             ChannelModeChange::Added(ref mc) => {
                 let (modechar, value) = mc.to_tuple();
@@ -479,6 +937,26 @@ impl ChannelModeChange {
                 let (modechar, value) = mc.to_tuple();
                 ('-', modechar, value)
             }
+            ChannelModeChange::ListQuery(modechar) => ('+', modechar, None),
         }
     }
 }
+
+impl UserModeChange {
+    fn to_tuple(&self) -> (char, char, Option<&str>) {
+        let (action, mode) = match *self {
+            UserModeChange::Added(ref mode) => ('+', mode),
+            UserModeChange::Removed(ref mode) => ('-', mode),
+        };
+        let (modechar, value) = match *mode {
+            UserMode::Invisible => ('i', None),
+            UserMode::Wallops => ('w', None),
+            UserMode::Oper => ('o', None),
+            UserMode::LocalOper => ('O', None),
+            UserMode::Registered => ('r', None),
+            UserMode::ServerNotices(ref mask) => ('s', mask.as_ref().map(|m| &m[..])),
+            UserMode::Other(c) => (c, None),
+        };
+        (action, modechar, value)
+    }
+}
@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+
+use nom;
+
+use ErrorKind;
+
+// Message tags: https://ircv3.net/specs/extensions/message-tags
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Tag<'a> {
+    pub key: Cow<'a, str>,
+    pub value: Option<Cow<'a, str>>,
+}
+
+impl<'a> Tag<'a> {
+    pub fn new<K>(key: K, value: Option<String>) -> Tag<'a>
+    where
+        K: Into<Cow<'a, str>>,
+    {
+        Tag {
+            key: key.into(),
+            value: value.map(Cow::Owned),
+        }
+    }
+
+    pub fn into_owned(self) -> Tag<'static> {
+        Tag {
+            key: Cow::Owned(self.key.into_owned()),
+            value: self.value.map(|value| Cow::Owned(value.into_owned())),
+        }
+    }
+
+    /// Client-only tags are prefixed with `+` and are relayed verbatim by the server.
+    pub fn is_client_only(&self) -> bool {
+        self.key.starts_with('+')
+    }
+
+    /// The vendor namespace of the key, e.g. `example.com` for `+example.com/foo`.
+    pub fn vendor(&self) -> Option<&str> {
+        let key = self.key.trim_start_matches('+');
+        key.rfind('/').map(|idx| &key[..idx])
+    }
+
+    /// The key without the client-only marker and vendor namespace.
+    pub fn name(&self) -> &str {
+        let key = self.key.trim_start_matches('+');
+        match key.rfind('/') {
+            Some(idx) => &key[idx + 1..],
+            None => key,
+        }
+    }
+}
+
+/// The tags of a message, in the order they were received.
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct Tags<'a>(Vec<Tag<'a>>);
+
+impl<'a> Tags<'a> {
+    pub fn new() -> Tags<'a> {
+        Tags(Vec::new())
+    }
+
+    pub fn into_owned(self) -> Tags<'static> {
+        Tags(self.0.into_iter().map(Tag::into_owned).collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, Tag<'a>> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Tag<'a>> {
+        self.0.iter().find(|tag| tag.key == key)
+    }
+
+    /// Returns the value of the tag, treating a tag without a value as having an empty one.
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.get(key).map(|tag| tag.value.as_deref().unwrap_or(""))
+    }
+
+    /// Sets the value of a tag, keeping its position if it is already present.
+    pub fn insert<K>(&mut self, key: K, value: Option<String>)
+    where
+        K: Into<Cow<'a, str>>,
+    {
+        let key = key.into();
+        match self.0.iter_mut().find(|tag| tag.key == key) {
+            Some(tag) => tag.value = value.map(Cow::Owned),
+            None => self.0.push(Tag::new(key, value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Tag<'a>> {
+        let idx = self.0.iter().position(|tag| tag.key == key)?;
+        Some(self.0.remove(idx))
+    }
+}
+
+impl<'a> From<Vec<Tag<'a>>> for Tags<'a> {
+    fn from(tags: Vec<Tag<'a>>) -> Tags<'a> {
+        Tags(tags)
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b Tags<'a> {
+    type Item = &'b Tag<'a>;
+    type IntoIter = ::std::slice::Iter<'b, Tag<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Unescapes a tag value, borrowing it when there is nothing to unescape.
+pub(crate) fn unescape_value(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            // A trailing lone backslash is dropped.
+            None => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+pub(crate) fn escape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => out.push_str("\\:"),
+            ' ' => out.push_str("\\s"),
+            '\\' => out.push_str("\\\\"),
+            '\r' => out.push_str("\\r"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn parse_tag(input: &str) -> Option<Tag<'_>> {
+    let mut parts = input.splitn(2, '=');
+    let key = parts.next().unwrap_or("");
+    if key.is_empty() || key == "+" {
+        return None;
+    }
+    let value = parts.next().map(unescape_value);
+    Some(Tag {
+        key: Cow::Borrowed(key),
+        value,
+    })
+}
+
+pub(crate) fn tags(input: &[u8]) -> nom::IResult<&[u8], Tags<'_>> {
+    let (rest, _) = try_parse!(input, tag!("@"));
+    let (rest, raw) = try_parse!(rest, take_till!(|c| b" \0\r\n".contains(&c)));
+    let tags = match String::from_utf8_lossy(raw) {
+        Cow::Borrowed(raw) => raw.split(';').filter_map(parse_tag).collect::<Vec<_>>(),
+        Cow::Owned(raw) => raw
+            .split(';')
+            .filter_map(|tag| parse_tag(tag).map(Tag::into_owned))
+            .collect(),
+    };
+    if tags.is_empty() {
+        return Err(nom::Err::Error(nom::Context::Code(
+            input,
+            ErrorKind::EmptyTags.code(),
+        )));
+    }
+    Ok((rest, Tags(tags)))
+}
@@ -0,0 +1,263 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+use std::str;
+
+/// A single-byte legacy character set still found on older networks.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Charset {
+    /// ISO-8859-1, where every byte maps to the code point of the same value.
+    Latin1,
+    /// Windows-1252, which is Latin-1 with printable characters in place of the C1 controls.
+    Cp1252,
+}
+
+// Windows-1252 0x80..0x9F. The five unassigned bytes map to their C1 control codes.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl Charset {
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        bytes
+            .iter()
+            .map(|&b| match *self {
+                Charset::Cp1252 if (0x80..0xA0).contains(&b) => CP1252_HIGH[usize::from(b - 0x80)],
+                _ => char::from(b),
+            })
+            .collect()
+    }
+}
+
+/// How the bytes of a parameter are turned into text.
+///
+/// The original bytes are kept alongside the text whichever decoder is used, see `Text`.
+#[derive(Clone, Copy, Debug)]
+pub enum TextDecoder {
+    /// UTF-8, replacing invalid sequences with U+FFFD.
+    Utf8,
+    /// UTF-8, decoding any parameter that is not valid UTF-8 with the given charset instead.
+    Utf8Fallback(Charset),
+    /// Always decode with the given charset.
+    Legacy(Charset),
+    /// A user-supplied decoder.
+    Custom(fn(&[u8]) -> String),
+}
+
+impl TextDecoder {
+    /// Decodes `bytes`, borrowing them when they are already valid text.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        match *self {
+            TextDecoder::Utf8 => String::from_utf8_lossy(bytes),
+            TextDecoder::Utf8Fallback(charset) => match str::from_utf8(bytes) {
+                Ok(text) => Cow::Borrowed(text),
+                Err(_) => Cow::Owned(charset.decode(bytes)),
+            },
+            TextDecoder::Legacy(charset) => match str::from_utf8(bytes) {
+                Ok(text) if bytes.is_ascii() => Cow::Borrowed(text),
+                _ => Cow::Owned(charset.decode(bytes)),
+            },
+            TextDecoder::Custom(decode) => Cow::Owned(decode(bytes)),
+        }
+    }
+}
+
+/// A parameter as received, together with its decoded text.
+///
+/// Dereferences to the text; the bytes are what gets written back when serializing, so
+/// parameters that did not decode cleanly are relayed unchanged. Two `Text`s are equal when
+/// their bytes are.
+#[derive(Clone, Debug)]
+pub struct Text<'a> {
+    repr: Repr<'a>,
+}
+
+#[derive(Clone, Debug)]
+enum Repr<'a> {
+    /// The bytes are valid UTF-8 and the text is exactly them, so only the text is kept.
+    Utf8(Cow<'a, str>),
+    /// The text was decoded from bytes that differ from it.
+    Decoded {
+        raw: Cow<'a, [u8]>,
+        text: Cow<'a, str>,
+    },
+}
+
+impl<'a> Text<'a> {
+    pub fn from_bytes<B>(raw: B, decoder: &TextDecoder) -> Text<'a>
+    where
+        B: Into<Cow<'a, [u8]>>,
+    {
+        match raw.into() {
+            Cow::Borrowed(raw) => match decoder.decode(raw) {
+                Cow::Borrowed(text) => Text::from(text),
+                Cow::Owned(text) => Text::decoded(Cow::Borrowed(raw), text),
+            },
+            Cow::Owned(raw) => {
+                let decoded = match decoder.decode(&raw) {
+                    Cow::Borrowed(_) => None,
+                    Cow::Owned(text) => Some(text),
+                };
+                match decoded {
+                    Some(text) => Text::decoded(Cow::Owned(raw), text),
+                    // The decoder only borrows bytes that are valid UTF-8.
+                    None => match String::from_utf8(raw) {
+                        Ok(text) => Text::from(text),
+                        Err(err) => {
+                            let raw = err.into_bytes();
+                            let text = String::from_utf8_lossy(&raw).into_owned();
+                            Text::decoded(Cow::Owned(raw), text)
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Keeps `raw` alongside `text` only if they differ.
+    fn decoded(raw: Cow<'a, [u8]>, text: String) -> Text<'a> {
+        if text.as_bytes() == &*raw {
+            return Text::from(text);
+        }
+        Text {
+            repr: Repr::Decoded {
+                raw,
+                text: Cow::Owned(text),
+            },
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.repr {
+            Repr::Utf8(ref text) => text.as_bytes(),
+            Repr::Decoded { ref raw, .. } => raw,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self.repr {
+            Repr::Utf8(ref text) => text,
+            Repr::Decoded { ref text, .. } => text,
+        }
+    }
+
+    /// Splits on `separator`, decoding each piece with `decoder`.
+    pub fn split(&self, separator: u8, decoder: &TextDecoder) -> Vec<Text<'a>> {
+        let borrowed = match self.repr {
+            Repr::Utf8(Cow::Borrowed(text)) => Some(text.as_bytes()),
+            Repr::Decoded {
+                raw: Cow::Borrowed(raw),
+                ..
+            } => Some(raw),
+            _ => None,
+        };
+        match borrowed {
+            Some(raw) => raw
+                .split(|b| *b == separator)
+                .map(|piece| Text::from_bytes(piece, decoder))
+                .collect(),
+            None => self
+                .as_bytes()
+                .split(|b| *b == separator)
+                .map(|piece| Text::from_bytes(piece.to_vec(), decoder))
+                .collect(),
+        }
+    }
+
+    /// A `Text` borrowing from this one.
+    pub fn borrow(&self) -> Text<'_> {
+        let repr = match self.repr {
+            Repr::Utf8(ref text) => Repr::Utf8(Cow::Borrowed(text)),
+            Repr::Decoded { ref raw, ref text } => Repr::Decoded {
+                raw: Cow::Borrowed(raw),
+                text: Cow::Borrowed(text),
+            },
+        };
+        Text { repr }
+    }
+
+    /// Concatenates `items` with `separator` between them, keeping the bytes of each.
+    pub fn join(items: &[Text], separator: char) -> Text<'static> {
+        let mut raw = Vec::new();
+        let mut text = String::new();
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                let mut buf = [0; 4];
+                raw.extend_from_slice(separator.encode_utf8(&mut buf).as_bytes());
+                text.push(separator);
+            }
+            raw.extend_from_slice(item.as_bytes());
+            text.push_str(item.as_str());
+        }
+        Text::decoded(Cow::Owned(raw), text)
+    }
+
+    /// Copies any borrowed data so that the text no longer refers to the input buffer.
+    pub fn into_owned(self) -> Text<'static> {
+        let repr = match self.repr {
+            Repr::Utf8(text) => Repr::Utf8(Cow::Owned(text.into_owned())),
+            Repr::Decoded { raw, text } => Repr::Decoded {
+                raw: Cow::Owned(raw.into_owned()),
+                text: Cow::Owned(text.into_owned()),
+            },
+        };
+        Text { repr }
+    }
+}
+
+impl<'a> Deref for Text<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a> fmt::Display for Text<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'a, 'b> PartialEq<Text<'b>> for Text<'a> {
+    fn eq(&self, other: &Text<'b>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<'a> Eq for Text<'a> {}
+
+impl<'a> PartialEq<str> for Text<'a> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for Text<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<'a> From<&'a str> for Text<'a> {
+    fn from(text: &'a str) -> Text<'a> {
+        Text::from(Cow::Borrowed(text))
+    }
+}
+
+impl<'a> From<String> for Text<'a> {
+    fn from(text: String) -> Text<'a> {
+        Text::from(Cow::Owned(text))
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Text<'a> {
+    fn from(text: Cow<'a, str>) -> Text<'a> {
+        Text {
+            repr: Repr::Utf8(text),
+        }
+    }
+}